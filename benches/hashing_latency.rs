@@ -0,0 +1,100 @@
+//! Measures how logins delay cheap requests such as `/api/me`, through the real router.
+//!
+//! Run with `cargo bench --bench hashing_latency`. The router runs on a single-threaded
+//! runtime, so that any hashing done on the runtime thread would show as `/api/me` latency.
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use login_with_axum::entity::UserStatus;
+use login_with_axum::password::PasswordHasher;
+use tower::ServiceExt as _;
+
+const LOGINS: usize = 32;
+const PROBES: usize = 200;
+const PROBE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Requests `/api/me` every [`PROBE_INTERVAL`] and records how long each one takes.
+async fn probe(router: &axum::Router, session: &str) -> Vec<Duration> {
+    let mut latencies = Vec::with_capacity(PROBES);
+    for _ in 0..PROBES {
+        let req = Request::get("/api/me")
+            .header(header::COOKIE, session)
+            .body(Body::empty())
+            .expect("request");
+        let start = Instant::now();
+        let res = router.clone().oneshot(req).await.expect("response");
+        latencies.push(start.elapsed());
+        assert_eq!(res.status(), StatusCode::OK);
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+    latencies
+}
+
+/// Keeps `LOGINS` logins in flight until `done` is set.
+async fn login_load(router: axum::Router, done: Arc<AtomicBool>) {
+    let csrf = support::csrf(&router).await;
+    let mut tasks = Vec::with_capacity(LOGINS);
+    for _ in 0..LOGINS {
+        let router = router.clone();
+        let csrf = csrf.clone();
+        let done = Arc::clone(&done);
+        tasks.push(tokio::spawn(async move {
+            while !done.load(Ordering::Relaxed) {
+                let res = support::login(&router, &csrf, "alice", "correct horse").await;
+                assert_eq!(res.status(), StatusCode::SEE_OTHER);
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.expect("login task");
+    }
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    sorted[(sorted.len() - 1) * p / 100]
+}
+
+fn report(label: &str, mut latencies: Vec<Duration>) {
+    latencies.sort_unstable();
+    println!(
+        "{label}: p50 {:?}, p99 {:?}, max {:?}",
+        percentile(&latencies, 50),
+        percentile(&latencies, 99),
+        latencies.last().copied().unwrap_or_default(),
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("runtime");
+    runtime.block_on(async {
+        let state = support::MemoryState::new(PasswordHasher::default());
+        state
+            .add_user("alice", "correct horse", UserStatus::Active)
+            .await;
+        let router = state.into_router();
+        let csrf = support::csrf(&router).await;
+        let res = support::login(&router, &csrf, "alice", "correct horse").await;
+        let session = support::set_cookies(&res).pop().expect("session cookie");
+
+        println!("/api/me latency:");
+        report("idle", probe(&router, &session).await);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let load = tokio::spawn(login_load(router.clone(), Arc::clone(&done)));
+        tokio::task::yield_now().await;
+        let latencies = probe(&router, &session).await;
+        done.store(true, Ordering::Relaxed);
+        load.await.expect("login load");
+        report(&format!("{LOGINS} concurrent logins"), latencies);
+    });
+}
@@ -0,0 +1,74 @@
+//! Embeds the built client into the binary with the `embed-assets` feature, and rebuilds when
+//! migrations are added.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Where `deno task build` writes the client, overridable at build time.
+const DEFAULT_DIR: &str = "dist";
+
+fn main() {
+    // `sqlx::migrate!` cannot tell cargo about new migration files itself
+    println!("cargo::rerun-if-changed=migrations");
+    println!("cargo::rerun-if-env-changed=EMBED_ASSETS_DIR");
+    if std::env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_none() {
+        return;
+    }
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let dir = manifest_dir.join(
+        std::env::var_os("EMBED_ASSETS_DIR").map_or_else(|| DEFAULT_DIR.into(), PathBuf::from),
+    );
+    println!("cargo::rerun-if-changed={}", dir.display());
+
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        collect(&dir, &mut files);
+    } else {
+        println!(
+            "cargo::warning=No client assets to embed in {}; build the client first",
+            dir.display()
+        );
+    }
+    files.sort();
+
+    let mut out = String::from("static FILES: &[File] = &[\n");
+    for path in &files {
+        let name = path
+            .strip_prefix(&dir)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let bytes = std::fs::read(path).unwrap();
+        writeln!(
+            out,
+            "    File {{ path: {name:?}, bytes: include_bytes!({:?}), etag: \"W/\\\"{:016x}\\\"\" }},",
+            path.display().to_string(),
+            fnv1a(&bytes),
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("assets.rs"), out).unwrap();
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(&path, files);
+        } else {
+            println!("cargo::rerun-if-changed={}", path.display());
+            files.push(path);
+        }
+    }
+}
+
+/// A content hash for `ETag`s; collisions only cost a stale cache entry.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
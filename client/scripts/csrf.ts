@@ -0,0 +1,22 @@
+/**
+ * Adds the CSRF token as a hidden field of the form, fetching it from the server.
+ * @param {HTMLFormElement} form form posted to the API
+ * @param {String} root root path of the application
+ */
+export async function addCsrfField(form: HTMLFormElement, root: string) {
+    const res = await fetch(`${root}/api/csrf-token`);
+    if (!res.ok) {
+        console.error("Failed to fetch CSRF token");
+        return;
+    }
+    const { token } = await res.json();
+    if (typeof token !== "string") {
+        console.error("Server responded invalid CSRF token");
+        return;
+    }
+    const input = document.createElement("input");
+    input.type = "hidden";
+    input.name = "csrf_token";
+    input.value = token;
+    form.appendChild(input);
+}
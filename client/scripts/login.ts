@@ -1,6 +1,7 @@
+import { addCsrfField } from "./csrf.ts";
 import { rootPath } from "./location.ts";
 
-function setupForm() {
+async function setupForm() {
     const root = rootPath("login.html");
     if (root !== undefined) {
         console.log(`Root path: "${root}"`);
@@ -14,6 +15,7 @@ function setupForm() {
         return;
     }
     form.action = `${root}/api/login`;
+    await addCsrfField(form, root);
 }
 
-setupForm();
+await setupForm();
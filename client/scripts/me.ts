@@ -1,9 +1,9 @@
+import { addCsrfField } from "./csrf.ts";
 import { rootPath } from "./location.ts";
 import User from "./user.ts";
 
 function nop() {}
 
-// deno-lint-ignore require-await
 async function setupForm() {
     const root = rootPath("me.html");
     if (root !== undefined) {
@@ -17,6 +17,7 @@ async function setupForm() {
         console.error("Logout form not found");
         return nop;
     }
+    await addCsrfField(form, root);
     return function () {
         form.action = `${root}/api/logout`;
     };
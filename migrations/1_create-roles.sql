@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS `roles` (
+    `name` VARCHAR(32) NOT NULL PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS `role_permissions` (
+    `role` VARCHAR(32) NOT NULL,
+    `permission` VARCHAR(64) NOT NULL,
+    PRIMARY KEY (`role`, `permission`)
+);
+
+CREATE TABLE IF NOT EXISTS `user_roles` (
+    `user_id` BINARY(16) NOT NULL,
+    `role` VARCHAR(32) NOT NULL,
+    PRIMARY KEY (`user_id`, `role`)
+);
+
+INSERT IGNORE INTO `roles` (`name`) VALUES ('admin');
+INSERT IGNORE INTO `role_permissions` (`role`, `permission`) VALUES ('admin', '*');
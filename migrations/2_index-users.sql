@@ -0,0 +1 @@
+CREATE INDEX `users_name_display_id` ON `users` (`name`, `display_id`);
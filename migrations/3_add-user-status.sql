@@ -0,0 +1,5 @@
+ALTER TABLE `users`
+    ADD COLUMN `status` VARCHAR(32) NOT NULL DEFAULT 'active',
+    ADD COLUMN `status_updated_at` DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6);
+
+CREATE INDEX `users_status_updated_at` ON `users` (`status`, `status_updated_at`);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS `audit_events` (
+    `id` BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `occurred_at` DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+    `kind` VARCHAR(32) NOT NULL,
+    `actor_id` BINARY(16) NULL,
+    `subject_id` BINARY(16) NULL,
+    `detail` VARCHAR(255) NULL,
+    `ip` VARCHAR(45) NULL,
+    `user_agent` VARCHAR(255) NULL,
+    `request_id` VARCHAR(64) NULL,
+    INDEX `audit_events_actor_id` (`actor_id`, `id`),
+    INDEX `audit_events_subject_id` (`subject_id`, `id`),
+    INDEX `audit_events_kind` (`kind`, `id`),
+    INDEX `audit_events_occurred_at` (`occurred_at`)
+);
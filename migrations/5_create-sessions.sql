@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS `sessions` (
+    `id` BINARY(16) NOT NULL PRIMARY KEY,
+    `user_id` BINARY(16) NOT NULL,
+    `user_agent` VARCHAR(255) NULL,
+    `ip` VARCHAR(45) NULL,
+    `created_at` DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+    `last_used_at` DATETIME(6) NOT NULL DEFAULT CURRENT_TIMESTAMP(6),
+    `expires_at` DATETIME(6) NOT NULL,
+    INDEX `sessions_user_id` (`user_id`, `expires_at`)
+);
@@ -0,0 +1 @@
+ALTER TABLE `user_passwords` MODIFY `psk` VARCHAR(255) NOT NULL;
@@ -0,0 +1,13 @@
+-- rows left behind by users purged before the constraints existed
+DELETE FROM `user_passwords` WHERE `user_id` NOT IN (SELECT `id` FROM `users`);
+DELETE FROM `user_roles` WHERE `user_id` NOT IN (SELECT `id` FROM `users`);
+DELETE FROM `sessions` WHERE `user_id` NOT IN (SELECT `id` FROM `users`);
+
+ALTER TABLE `user_passwords`
+    ADD CONSTRAINT `user_passwords_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE;
+
+ALTER TABLE `user_roles`
+    ADD CONSTRAINT `user_roles_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE;
+
+ALTER TABLE `sessions`
+    ADD CONSTRAINT `sessions_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE;
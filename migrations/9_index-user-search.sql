@@ -0,0 +1,3 @@
+-- searching and sorting by `display_id`, skipping deleted users within the index;
+-- `users_name_display_id` serves the same for `name`
+CREATE INDEX `users_display_id_status` ON `users` (`display_id`, `status`);
@@ -0,0 +1,86 @@
+use crate::Failure;
+use crate::entity::{AuditEvent, RecordAuditEventParams, SearchAuditEventsParams};
+
+/// Emits audit events as `tracing` events under the `audit` target.
+///
+/// Nothing is retained, so searching is not supported.
+#[must_use]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingAuditLog;
+
+impl<Context> crate::entity::AuditLog<Context> for TracingAuditLog
+where
+    Context: Send + Sync,
+{
+    async fn record_audit_event(
+        &self,
+        _ctx: Context,
+        params: RecordAuditEventParams,
+    ) -> Result<(), Failure> {
+        let RecordAuditEventParams {
+            kind,
+            actor,
+            subject,
+            detail,
+            ip,
+            user_agent,
+            request_id,
+        } = params;
+        tracing::info!(
+            target: "audit",
+            kind = kind.as_str(),
+            actor = actor.map(|a| a.0.to_string()),
+            subject = subject.map(|s| s.0.to_string()),
+            detail,
+            ip = ip.map(|ip| ip.to_string()),
+            user_agent,
+            request_id,
+        );
+        Ok(())
+    }
+
+    async fn search_audit_events(
+        &self,
+        _ctx: Context,
+        _params: SearchAuditEventsParams,
+    ) -> Result<Vec<AuditEvent>, Failure> {
+        Err(Failure::not_found(
+            "Audit events are not retained by the tracing audit log",
+        ))
+    }
+}
+
+/// The audit log selected at startup.
+#[must_use]
+#[derive(Debug, Clone)]
+pub enum AuditLogger {
+    Database(crate::Repository),
+    Tracing(TracingAuditLog),
+}
+
+impl<Context> crate::entity::AuditLog<Context> for AuditLogger
+where
+    Context: crate::repository::AsMySqlPool,
+{
+    async fn record_audit_event(
+        &self,
+        ctx: Context,
+        params: RecordAuditEventParams,
+    ) -> Result<(), Failure> {
+        match self {
+            Self::Database(repo) => repo.record_audit_event(ctx, params).await,
+            Self::Tracing(log) => log.record_audit_event(ctx, params).await,
+        }
+    }
+
+    async fn search_audit_events(
+        &self,
+        ctx: Context,
+        params: SearchAuditEventsParams,
+    ) -> Result<Vec<AuditEvent>, Failure> {
+        match self {
+            Self::Database(repo) => repo.search_audit_events(ctx, params).await,
+            Self::Tracing(log) => log.search_audit_events(ctx, params).await,
+        }
+    }
+}
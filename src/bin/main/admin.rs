@@ -0,0 +1,286 @@
+//! Admin subcommands, going through the same registry and credential manager as the API.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+use anyhow::Context;
+use clap::Subcommand;
+use login_with_axum as lib;
+
+use lib::entity::{self, ProvideCredentialManager, ProvideUserRegistry};
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply pending migrations (default).
+    Up,
+    /// List migrations and whether they are applied.
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// Register a user, reading the password from stdin.
+    Create {
+        display_id: String,
+        /// Defaults to the display id.
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List users.
+    List {
+        /// Prefix of the display id or name.
+        #[arg(long)]
+        search: Option<String>,
+        #[arg(long, default_value_t = entity::GetUsersParams::DEFAULT_LIMIT)]
+        limit: u32,
+        /// List soft-deleted users too.
+        #[arg(long)]
+        include_deleted: bool,
+    },
+    /// Suspend a user, rejecting its existing and future sessions.
+    Disable { display_id: String },
+    /// Set a user's password, reading it from stdin.
+    ResetPassword { display_id: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCommand {
+    /// Issue a credential for a user, as if it had logged in.
+    Issue { display_id: String },
+    /// Check a credential and print its principal.
+    Inspect { token: String },
+    /// Revoke the session behind a credential.
+    Revoke { token: String },
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+pub enum KeysCommand {
+    /// Print a random key suitable for `JWT_KEY`.
+    Generate {
+        #[arg(long, default_value_t = 64)]
+        bytes: usize,
+    },
+}
+
+pub async fn migrate(state: &lib::State, command: Option<MigrateCommand>) -> anyhow::Result<()> {
+    match command.unwrap_or(MigrateCommand::Up) {
+        MigrateCommand::Up => {
+            state.migrate().await?;
+            println!("Database is up to date");
+        }
+        MigrateCommand::Status => {
+            for m in state.migration_status().await? {
+                let status = if m.applied { "applied" } else { "pending" };
+                println!("{:>4}  {status:<8} {}", m.version, m.description);
+            }
+            state.check_schema().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a password from the first line of stdin, prompting when it is a terminal.
+fn read_password() -> anyhow::Result<lib::Secret> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+        std::io::stderr().flush()?;
+    }
+    let mut password = String::new();
+    stdin
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read password")?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    anyhow::ensure!(!password.is_empty(), "Password must not be empty");
+    Ok(password.into())
+}
+
+async fn get_user<S>(state: &S, display_id: String) -> anyhow::Result<entity::User>
+where
+    S: ProvideUserRegistry,
+{
+    let params = entity::GetUserParams::ByDisplayId(display_id);
+    Ok(state.get_user(params).await?)
+}
+
+pub async fn user<S>(state: &S, command: UserCommand) -> anyhow::Result<()>
+where
+    S: ProvideUserRegistry,
+{
+    match command {
+        UserCommand::Create { display_id, name } => {
+            let raw_password = read_password()?;
+            let params = entity::RegisterUserParams {
+                name: name.unwrap_or_else(|| display_id.clone()),
+                display_id,
+                raw_password,
+            };
+            let user = state.register_user(params).await?;
+            println!("{}", user.id.0);
+        }
+        UserCommand::List {
+            search,
+            limit,
+            include_deleted,
+        } => {
+            let params = entity::GetUsersParams {
+                search,
+                limit,
+                include_deleted,
+                ..Default::default()
+            };
+            let page = state.get_users(params).await?;
+            for u in &page.users {
+                let status = u.status.as_str();
+                println!("{}  {status:<20} {}  {}", u.id.0, u.display_id, u.name);
+            }
+            let shown = page.users.len();
+            match page.total {
+                Some(total) if total >= entity::UserPage::COUNT_LIMIT => {
+                    eprintln!("{shown} of at least {total} users");
+                }
+                Some(total) => eprintln!("{shown} of {total} users"),
+                None => eprintln!("{shown} users"),
+            }
+        }
+        UserCommand::Disable { display_id } => {
+            let user = get_user(state, display_id).await?;
+            let params = entity::SetUserStatusParams {
+                id: user.id,
+                status: entity::UserStatus::Suspended,
+            };
+            state.set_user_status(params).await?;
+        }
+        UserCommand::ResetPassword { display_id } => {
+            let user = get_user(state, display_id).await?;
+            let new_raw = read_password()?;
+            let params = entity::UpdateUserPasswordParams {
+                user_id: user.id,
+                new_raw,
+            };
+            state.update_user_password(params).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn token<S>(state: &S, command: TokenCommand) -> anyhow::Result<()>
+where
+    S: ProvideUserRegistry + ProvideCredentialManager,
+{
+    match command {
+        TokenCommand::Issue { display_id } => {
+            let user = get_user(state, display_id).await?;
+            user.status.ensure_active()?;
+            let roles = state.get_user_roles(user.id).await?;
+            let params = entity::MakeCredentialParams {
+                user_id: user.id,
+                roles,
+                user_agent: Some(format!("{} cli", env!("CARGO_PKG_NAME"))),
+                ip: None,
+            };
+            let entity::Credential(token) = state.make_credential(params).await?;
+            println!("{token}");
+        }
+        TokenCommand::Inspect { token } => {
+            let principal = state.check_credential(entity::Credential(token)).await?;
+            println!("{}", serde_json::to_string_pretty(&principal)?);
+        }
+        TokenCommand::Revoke { token } => {
+            state.revoke_credential(entity::Credential(token)).await?;
+        }
+    }
+    Ok(())
+}
+
+pub fn keys(command: KeysCommand) -> anyhow::Result<()> {
+    match command {
+        KeysCommand::Generate { bytes } => {
+            anyhow::ensure!(
+                bytes >= 32,
+                "Keys shorter than 32 bytes are too weak for HS256"
+            );
+            let mut key = vec![0; bytes];
+            getrandom::fill(&mut key)
+                .map_err(|e| anyhow::anyhow!("Failed to get randomness: {e}"))?;
+            let mut out = std::io::stdout().lock();
+            for b in key {
+                write!(out, "{b:02x}")?;
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::entity::UserStatus;
+    use lib::password::{Algorithm, PasswordHasher};
+
+    use super::*;
+    use crate::support;
+
+    async fn state() -> support::MemoryState {
+        let state =
+            support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+        state
+            .add_user("alice", "correct horse", UserStatus::Active)
+            .await;
+        state
+    }
+
+    #[tokio::test]
+    async fn disable_suspends_the_user() {
+        let state = state().await;
+        let command = UserCommand::Disable {
+            display_id: "alice".to_string(),
+        };
+        user(&state, command).await.unwrap();
+        let alice = get_user(&state, "alice".to_string()).await.unwrap();
+        assert_eq!(alice.status, UserStatus::Suspended);
+
+        // a disabled user gets no new credentials
+        let command = TokenCommand::Issue {
+            display_id: "alice".to_string(),
+        };
+        assert!(token(&state, command).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unknown_users_are_reported() {
+        let state = state().await;
+        let command = UserCommand::Disable {
+            display_id: "nobody".to_string(),
+        };
+        let e = user(&state, command).await.unwrap_err();
+        assert!(e.to_string().contains("User not found"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn revoked_tokens_fail_inspection() {
+        let state = state().await;
+        let alice = get_user(&state, "alice".to_string()).await.unwrap();
+        let params = entity::MakeCredentialParams {
+            user_id: alice.id,
+            roles: Vec::new(),
+            user_agent: None,
+            ip: None,
+        };
+        let entity::Credential(credential) = state.make_credential(params).await.unwrap();
+        let inspect = || TokenCommand::Inspect {
+            token: credential.clone(),
+        };
+        token(&state, inspect()).await.unwrap();
+        let revoke = TokenCommand::Revoke {
+            token: credential.clone(),
+        };
+        token(&state, revoke).await.unwrap();
+        assert!(token(&state, inspect()).await.is_err());
+    }
+
+    #[test]
+    fn short_keys_are_refused() {
+        assert!(keys(KeysCommand::Generate { bytes: 16 }).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+mod admin;
+#[cfg(test)]
+#[path = "../../../tests/support/mod.rs"]
+mod support;
+
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use login_with_axum as lib;
+
+/// Login server with admin tooling sharing its configuration.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// TOML configuration file, overridden by environment variables.
+    #[arg(long, global = true, env = lib::config::FILE_ENV)]
+    config: Option<std::path::PathBuf>,
+    /// Print the effective configuration with secrets redacted, then exit.
+    #[arg(long)]
+    print_config: bool,
+    /// Defaults to `serve`.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Migrate the database and serve the API.
+    Serve,
+    /// Inspect or apply database migrations.
+    Migrate {
+        #[command(subcommand)]
+        command: Option<admin::MigrateCommand>,
+    },
+    /// Manage users.
+    User {
+        #[command(subcommand)]
+        command: admin::UserCommand,
+    },
+    /// Issue, inspect and revoke credentials.
+    Token {
+        #[command(subcommand)]
+        command: admin::TokenCommand,
+    },
+    /// Generate key material.
+    Keys {
+        #[command(subcommand)]
+        command: admin::KeysCommand,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    if let Some(Command::Keys { command }) = cli.command {
+        return admin::keys(command);
+    }
+    let config = lib::Config::load(cli.config.as_deref())?;
+    let is_server = matches!(cli.command, None | Some(Command::Serve));
+    let worker_threads = if is_server {
+        config.server.worker_threads
+    } else {
+        1
+    };
+    runtime(worker_threads)?.block_on(run(cli, config, is_server))
+}
+
+fn runtime(worker_threads: usize) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = if worker_threads == 1 {
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        if worker_threads > 0 {
+            builder.worker_threads(worker_threads);
+        }
+        builder
+    };
+    builder.enable_all().build()
+}
+
+async fn run(cli: Cli, config: lib::Config, is_server: bool) -> anyhow::Result<()> {
+    // keep admin output readable; the server logs at info by default
+    let default_filter = if is_server { "info" } else { "warn" };
+    let _telemetry = lib::telemetry::init(&config.telemetry, default_filter)?;
+    if cli.print_config {
+        print!("{}", config.to_redacted_toml()?);
+        return Ok(());
+    }
+    let pool = config
+        .connect_database()
+        .await
+        .inspect_err(|e| tracing::error!("{e:?}"))?;
+    let state = lib::State::new(config.state_init(pool)?);
+    match cli.command {
+        None | Some(Command::Serve) => serve(&config, state).await,
+        Some(Command::Migrate { command }) => admin::migrate(&state, command).await,
+        Some(Command::User { command }) => admin::user(&state, command).await,
+        Some(Command::Token { command }) => admin::token(&state, command).await,
+        Some(Command::Keys { .. }) => unreachable!("handled before loading the configuration"),
+    }
+}
+
+async fn serve(config: &lib::Config, state: lib::State) -> anyhow::Result<()> {
+    use lib::health::HealthCheck;
+
+    state.setup().await?;
+    let state = Arc::new(state);
+    let (retention, interval) = config.purge_schedule();
+    tokio::spawn({
+        let state = Arc::clone(&state);
+        async move {
+            lib::purge_deleted_users_periodically(&*state, retention, interval).await;
+        }
+    });
+    let readiness = state.readiness().clone();
+    let app = lib::make_router(state);
+    let drain_delay = config.drain_delay();
+    let (stopping_tx, stopping) = tokio::sync::oneshot::channel();
+    let shutdown = async move {
+        lib::signal_handler(readiness, drain_delay).await;
+        let _ = stopping_tx.send(());
+    };
+    let shutdown_timeout = config.shutdown_timeout();
+    tokio::select! {
+        result = listen(config, app, shutdown) => result,
+        Ok(()) = stopping => {
+            tokio::time::sleep(shutdown_timeout).await;
+            tracing::warn!(?shutdown_timeout, "Dropping requests still open after the shutdown timeout");
+            Ok(())
+        }
+    }
+}
+
+async fn listen(
+    config: &lib::Config,
+    app: axum::Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    use lib::config::BindAddress;
+    use lib::listener::PreparedListener;
+
+    #[cfg(not(feature = "tls"))]
+    anyhow::ensure!(
+        config.tls.is_none(),
+        "tls.cert is set, but this build lacks the tls feature"
+    );
+    let server = &config.server;
+    match &server.address {
+        BindAddress::Ip(ip) => {
+            let addr = std::net::SocketAddr::new(*ip, server.port);
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            let proxy_protocol = config.proxy.protocol;
+            let tls = config.tls.is_some();
+            tracing::info!(%addr, tls, proxy_protocol, "Listening");
+            #[cfg(feature = "tls")]
+            if let Some(tls) = &config.tls {
+                return serve_tls(config, listener, tls, app, shutdown).await;
+            }
+            if proxy_protocol {
+                let trusted = proxy_protocol_trust(config);
+                let listener = PreparedListener::new(listener, move |stream, peer| {
+                    proxy_header(stream, peer, trusted.clone())
+                })?;
+                return serve_prepared(listener, app, shutdown).await;
+            }
+            let app = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await?;
+        }
+        #[cfg(unix)]
+        BindAddress::Unix(path) => {
+            use std::os::unix::fs::FileTypeExt;
+
+            // a socket left behind by a previous run would fail the bind
+            if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                std::fs::remove_file(path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            tracing::info!(path = %path.display(), "Listening");
+            axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(shutdown)
+                .await?;
+        }
+        #[cfg(not(unix))]
+        BindAddress::Unix(_) => anyhow::bail!("Unix sockets are not supported on this platform"),
+    }
+    Ok(())
+}
+
+/// The proxies whose PROXY protocol headers are read, if enabled.
+fn proxy_protocol_trust(config: &lib::Config) -> Option<Arc<lib::proxy::TrustedProxies>> {
+    config
+        .proxy
+        .protocol
+        .then(|| Arc::new(config.trusted_proxies()))
+}
+
+/// Reads the PROXY protocol header when `trusted` is set, returning the client address.
+async fn proxy_header(
+    mut stream: tokio::net::TcpStream,
+    peer: std::net::SocketAddr,
+    trusted: Option<Arc<lib::proxy::TrustedProxies>>,
+) -> std::io::Result<(tokio::net::TcpStream, std::net::SocketAddr)> {
+    let client = match &trusted {
+        Some(trusted) => lib::proxy::read_header(&mut stream, peer, trusted).await?,
+        None => peer,
+    };
+    Ok((stream, client))
+}
+
+async fn serve_prepared<Io>(
+    listener: lib::listener::PreparedListener<Io>,
+    app: axum::Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()>
+where
+    Io: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    use axum::serve::ListenerExt;
+
+    // tapping passes the client address on to `ConnectInfo`
+    let listener = listener.tap_io(|_| {});
+    let app = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+async fn serve_tls(
+    config: &lib::Config,
+    listener: tokio::net::TcpListener,
+    tls: &lib::config::TlsConfig,
+    app: axum::Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    use lib::listener::PreparedListener;
+
+    let addr = listener.local_addr()?;
+    let acceptor = lib::tls::acceptor(tls)?;
+    let trusted = proxy_protocol_trust(config);
+    if let Some(port) = tls.redirect_port {
+        let redirect_addr = std::net::SocketAddr::new(addr.ip(), port);
+        let redirect = tokio::net::TcpListener::bind(redirect_addr).await?;
+        tracing::info!(addr = %redirect_addr, "Redirecting to HTTPS");
+        let trusted = trusted.clone();
+        let redirect = PreparedListener::new(redirect, move |stream, peer| {
+            proxy_header(stream, peer, trusted.clone())
+        })?;
+        let app = lib::tls::redirect_router(addr.port());
+        tokio::spawn(async move {
+            if let Err(e) = serve_prepared(redirect, app, std::future::pending()).await {
+                tracing::error!("{e}");
+            }
+        });
+    }
+    let listener = PreparedListener::new(listener, move |stream, peer| {
+        let acceptor = acceptor.clone();
+        let trusted = trusted.clone();
+        async move {
+            let (stream, client) = proxy_header(stream, peer, trusted).await?;
+            Ok((acceptor.accept(stream).await?, client))
+        }
+    })?;
+    // requests on this listener came over TLS, unless a trusted proxy reports another scheme
+    let app = app.layer(axum::Extension(lib::TlsConnection));
+    serve_prepared(listener, app, shutdown).await
+}
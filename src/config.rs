@@ -0,0 +1,1267 @@
+//! Typed configuration, loaded from a TOML file layered with environment variables.
+//!
+//! Every setting has a key in the file, such as `jwt.lifetime`, and an environment variable,
+//! such as `JWT_LIFETIME`, which takes precedence. Secrets can also be read from the file named
+//! by `<VAR>_FILE` or `<key>_file`, so `JWT_KEY_FILE=/run/secrets/jwt` works alongside `JWT_KEY`.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::Secret;
+use crate::password::Algorithm;
+
+/// Environment variable naming the configuration file.
+pub const FILE_ENV: &str = "CONFIG_FILE";
+
+#[must_use]
+#[derive(Debug, Clone, Serialize)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub tls: Option<TlsConfig>,
+    pub proxy: ProxyConfig,
+    pub assets: AssetsConfig,
+    pub database: DatabaseConfig,
+    pub jwt: JwtConfig,
+    pub password: PasswordConfig,
+    pub cookie: CookieConfig,
+    pub csrf: CsrfConfig,
+    pub cors: CorsConfig,
+    pub security: SecurityConfig,
+    pub audit: AuditConfig,
+    pub purge: PurgeConfig,
+    pub telemetry: TelemetryConfig,
+    pub admin: Option<AdminConfig>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerConfig {
+    pub address: BindAddress,
+    /// Ignored when listening on a Unix socket.
+    pub port: u16,
+    /// Always starts and ends with `/`.
+    pub path_prefix: String,
+    /// Runtime threads serving requests; 0, the default, starts one per CPU and 1 runs everything
+    /// on the main thread.
+    pub worker_threads: usize,
+    /// Seconds.
+    pub request_timeout: u64,
+    /// Seconds for routes hashing a password.
+    pub hashing_timeout: u64,
+    /// Bytes.
+    pub body_limit: usize,
+    pub concurrency_limit: usize,
+    /// Seconds to keep serving with failing readiness after a shutdown signal.
+    pub drain_delay: u64,
+    /// Seconds open requests may take to finish after the listener closes.
+    pub shutdown_timeout: u64,
+}
+
+/// An IP address to listen on together with `server.port`, or `unix:<path>` for a Unix socket.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BindAddress {
+    Ip(IpAddr),
+    Unix(PathBuf),
+}
+
+impl Default for BindAddress {
+    fn default() -> Self {
+        Self::Ip(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+}
+
+impl FromStr for BindAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            anyhow::ensure!(!path.is_empty(), "expected a path after unix:");
+            return Ok(Self::Unix(path.into()));
+        }
+        // accept IPv6 addresses in brackets, as written in URLs
+        let ip = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .unwrap_or(s);
+        ip.parse()
+            .map(Self::Ip)
+            .map_err(|_| anyhow::anyhow!("expected an IP address or unix:<path>"))
+    }
+}
+
+impl fmt::Display for BindAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "{ip}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BindAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for BindAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Serves HTTPS on `server.port` when set.
+#[derive(Debug, Clone, Serialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first; reloaded when it changes or on SIGHUP.
+    pub cert: PathBuf,
+    /// PEM private key, reloaded together with `cert`.
+    pub key: PathBuf,
+    /// Port of a plain HTTP listener redirecting to HTTPS.
+    pub redirect_port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyConfig {
+    /// Addresses or ranges like `10.0.0.0/8` whose forwarding headers are believed.
+    pub trusted: Vec<String>,
+    /// Expect a PROXY protocol v1 or v2 header from a trusted proxy on every connection.
+    pub protocol: bool,
+    /// Believe forwarding headers from peers on the Unix socket `server.address` names.
+    pub trust_unix_socket: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetsConfig {
+    /// The client build, served when not embedded; relative to the working directory.
+    pub dir: PathBuf,
+    /// Serve the client built into the binary; needs the `embed-assets` feature.
+    pub embedded: bool,
+    /// Answer unknown paths with `index.html` instead of `404.html`.
+    pub spa_fallback: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseConfig {
+    pub hostname: String,
+    pub port: u16,
+    pub user: String,
+    pub password: Secret,
+    pub database: String,
+    /// Apply pending migrations on startup; otherwise refuse to serve until they are applied.
+    pub auto_migrate: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JwtConfig {
+    pub issuer: String,
+    pub key: Secret,
+    /// Seconds.
+    pub lifetime: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordConfig {
+    pub algorithm: Algorithm,
+    pub bcrypt_cost: u32,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub hashing_workers: usize,
+    pub hashing_queue_depth: usize,
+    /// Passwords to reject besides the bundled list, one per line.
+    pub common_passwords: Option<PathBuf>,
+    pub range_dir: Option<PathBuf>,
+    pub min_breach_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CookieConfig {
+    pub name: String,
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub domain: Option<String>,
+    pub host_prefix: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+impl FromStr for SameSite {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "lax" => Ok(Self::Lax),
+            "none" => Ok(Self::None),
+            _ => Err(anyhow::anyhow!("expected strict, lax or none")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CsrfConfig {
+    pub trusted_origins: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorsConfig {
+    /// Origins like `https://app.example.com`, `https://*.example.com` or `*`; none disables CORS.
+    pub origins: Vec<String>,
+    /// Lets the allowed origins send the session cookie, and exempts them from the CSRF origin
+    /// check; their requests still need the CSRF token.
+    pub credentials: bool,
+    pub methods: Vec<String>,
+    pub headers: Vec<String>,
+    /// Seconds browsers may cache a preflight response.
+    pub max_age: u64,
+}
+
+/// Header values where an empty string leaves the header out.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityConfig {
+    pub content_security_policy: String,
+    /// The CSP `frame-ancestors` sources, also sent as `X-Frame-Options` for `'none'` or `'self'`.
+    pub frame_ancestors: String,
+    pub strict_transport_security: String,
+    pub nosniff: bool,
+    pub referrer_policy: String,
+    pub cache_control: String,
+    /// Overrides by path prefix, applied in order after `no-store` for `/api/`; file only.
+    pub routes: Vec<RouteHeadersConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteHeadersConfig {
+    /// Relative to `server.path_prefix`, like `/api/`.
+    pub path: String,
+    pub content_security_policy: Option<String>,
+    pub frame_ancestors: Option<String>,
+    pub strict_transport_security: Option<String>,
+    pub nosniff: Option<bool>,
+    pub referrer_policy: Option<String>,
+    pub cache_control: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditConfig {
+    pub backend: AuditBackend,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditBackend {
+    #[default]
+    Database,
+    Tracing,
+}
+
+impl FromStr for AuditBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "database" => Ok(Self::Database),
+            "tracing" => Ok(Self::Tracing),
+            _ => Err(anyhow::anyhow!("expected database or tracing")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeConfig {
+    /// Seconds deleted users are kept before being purged.
+    pub retention: u64,
+    /// Seconds between purges.
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryConfig {
+    pub log_format: LogFormat,
+    /// Base URL of an OTLP/HTTP collector; traces are exported only when set.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the current span.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow::anyhow!("expected text or json")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminConfig {
+    pub display_id: String,
+    pub name: String,
+    pub password: Secret,
+}
+
+/// Every problem found while loading a [`Config`].
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads settings from the file and environment, collecting problems instead of stopping early.
+struct Loader {
+    file: toml::Table,
+    problems: Vec<String>,
+    /// File keys and env vars with a problem, so that they are not also reported as missing.
+    failed: HashSet<String>,
+    /// File keys looked up, whether or not they were set; any other key is unknown.
+    known: HashSet<String>,
+}
+
+impl Loader {
+    fn new(file: toml::Table, problems: Vec<String>) -> Self {
+        Self {
+            file,
+            problems,
+            failed: HashSet::new(),
+            known: HashSet::new(),
+        }
+    }
+
+    fn lookup(&mut self, key: &str) -> Option<&toml::Value> {
+        self.known.insert(key.to_string());
+        let (table, name) = match key.rsplit_once('.') {
+            Some((section, name)) => (self.file.get(section)?.as_table()?, name),
+            None => (&self.file, key),
+        };
+        table.get(name)
+    }
+
+    /// Marks `keys` as known though unused, as the setting they belong to is off.
+    fn ignore(&mut self, keys: &[&str]) {
+        self.known.extend(keys.iter().map(ToString::to_string));
+    }
+
+    /// Records a problem with the file key or env var `source`.
+    fn problem(&mut self, source: &str, problem: impl fmt::Display) {
+        self.problems.push(format!("{source}: {problem}"));
+        self.failed.insert(source.to_string());
+    }
+
+    fn env(name: &str) -> Option<(&str, String)> {
+        std::env::var(name).ok().map(|v| (name, v))
+    }
+
+    /// Reads `key`, preferring the first of `envs` that is set.
+    fn optional<T, E>(&mut self, key: &str, envs: &[E]) -> Option<T>
+    where
+        T: FromStr + DeserializeOwned,
+        T::Err: fmt::Display,
+        E: AsRef<str>,
+    {
+        // looked up first, so that a key overridden by the environment is still known
+        let value = self.lookup(key).cloned();
+        if let Some((name, value)) = envs.iter().find_map(|name| Self::env(name.as_ref())) {
+            return value.parse().map_err(|e| self.problem(name, e)).ok();
+        }
+        value?.try_into().map_err(|e| self.problem(key, e)).ok()
+    }
+
+    fn required<T, E>(&mut self, key: &str, envs: &[E]) -> Option<T>
+    where
+        T: FromStr + DeserializeOwned,
+        T::Err: fmt::Display,
+        E: AsRef<str>,
+    {
+        let value = self.optional(key, envs);
+        if value.is_none() && !self.has_problem(key, envs) {
+            let envs: Vec<_> = envs.iter().map(AsRef::as_ref).collect();
+            self.problems
+                .push(format!("{key} is required (or env {})", envs.join(", ")));
+        }
+        value
+    }
+
+    fn or<T, E>(&mut self, key: &str, envs: &[E], default: T) -> T
+    where
+        T: FromStr + DeserializeOwned,
+        T::Err: fmt::Display,
+        E: AsRef<str>,
+    {
+        self.optional(key, envs).unwrap_or(default)
+    }
+
+    fn has_problem<E: AsRef<str>>(&self, key: &str, envs: &[E]) -> bool {
+        self.failed.contains(key) || envs.iter().any(|e| self.failed.contains(e.as_ref()))
+    }
+
+    /// Reads a secret inline, or from the file named by `<VAR>_FILE` or `<key>_file`.
+    fn secret<E: AsRef<str>>(&mut self, key: &str, envs: &[E]) -> Option<String> {
+        let value = self.optional(key, envs);
+        let (file_key, file_envs) = Self::secret_file(key, envs);
+        // known either way, as the inline value takes precedence over the file
+        self.lookup(&file_key);
+        if value.is_some() {
+            return value;
+        }
+        let path: PathBuf = self.optional(&file_key, &file_envs)?;
+        std::fs::read_to_string(&path)
+            .map(|s| s.trim_end_matches(['\r', '\n']).to_string())
+            .map_err(|e| self.problem(&file_key, format!("Failed to read {}: {e}", path.display())))
+            .ok()
+    }
+
+    /// The key and env vars naming a file that holds the secret `key`.
+    fn secret_file<E: AsRef<str>>(key: &str, envs: &[E]) -> (String, Vec<String>) {
+        let file_envs = envs
+            .iter()
+            .map(|e| format!("{}_FILE", e.as_ref()))
+            .collect();
+        (format!("{key}_file"), file_envs)
+    }
+
+    fn required_secret<E: AsRef<str>>(&mut self, key: &str, envs: &[E]) -> Option<Secret> {
+        let value = self.secret(key, envs);
+        let (file_key, file_envs) = Self::secret_file(key, envs);
+        if value.is_none()
+            && !self.has_problem(key, envs)
+            && !self.has_problem(&file_key, &file_envs)
+        {
+            let env = envs.first().map_or("", AsRef::as_ref);
+            self.problems
+                .push(format!("{key} is required (or env {env} or {env}_FILE)"));
+        }
+        value.map(Secret::new)
+    }
+
+    /// Reads a list from a file array or a comma-separated env var.
+    fn list(&mut self, key: &str, env: &str) -> Vec<String> {
+        let value = self.lookup(key).cloned();
+        if let Some((_, value)) = Self::env(env) {
+            return value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        let Some(value) = value else {
+            return Vec::new();
+        };
+        value
+            .try_into()
+            .map_err(|e| self.problem(key, e))
+            .unwrap_or_default()
+    }
+
+    /// Reads a structured value with no environment form, like an array of tables.
+    fn file_only<T: DeserializeOwned>(&mut self, key: &str) -> Option<T> {
+        let value = self.lookup(key)?.clone();
+        value.try_into().map_err(|e| self.problem(key, e)).ok()
+    }
+
+    fn check(&mut self, ok: bool, problem: impl FnOnce() -> String) {
+        if !ok {
+            self.problems.push(problem());
+        }
+    }
+
+    /// Reports the keys in the file that no setting reads, suggesting the closest known key.
+    fn check_unknown_keys(&mut self) {
+        let mut keys = Vec::new();
+        for (name, value) in &self.file {
+            match value.as_table() {
+                Some(table) => keys.extend(table.keys().map(|k| format!("{name}.{k}"))),
+                None => keys.push(name.clone()),
+            }
+        }
+        for key in keys {
+            if self.known.contains(&key) {
+                continue;
+            }
+            let suggestion = self
+                .known
+                .iter()
+                .map(|known| (edit_distance(&key, known), known))
+                .filter(|&(distance, _)| distance <= 2)
+                .min();
+            match suggestion {
+                Some((_, known)) => {
+                    let problem = format!("unknown key, did you mean {known}?");
+                    self.problem(&key, problem);
+                }
+                None => self.problem(&key, "unknown key"),
+            }
+        }
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut row: Vec<_> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn normalize_path_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{prefix}/")
+    }
+}
+
+impl ServerConfig {
+    fn read(l: &mut Loader) -> Self {
+        let path_prefix: String = l.or("server.path_prefix", &["PREFIX"], String::new());
+        let limits = crate::Limits::default();
+        Self {
+            address: l.or("server.address", &["BIND_ADDRESS"], BindAddress::default()),
+            port: l.or("server.port", &["PORT"], 4176),
+            path_prefix: normalize_path_prefix(&path_prefix),
+            worker_threads: l.or("server.worker_threads", &["WORKER_THREADS"], 0),
+            request_timeout: l.or(
+                "server.request_timeout",
+                &["REQUEST_TIMEOUT"],
+                limits.request_timeout.as_secs(),
+            ),
+            hashing_timeout: l.or(
+                "server.hashing_timeout",
+                &["HASHING_TIMEOUT"],
+                limits.hashing_timeout.as_secs(),
+            ),
+            body_limit: l.or("server.body_limit", &["BODY_LIMIT"], limits.body_limit),
+            concurrency_limit: l.or(
+                "server.concurrency_limit",
+                &["CONCURRENCY_LIMIT"],
+                limits.concurrency_limit,
+            ),
+            drain_delay: l.or("server.drain_delay", &["DRAIN_DELAY"], 5),
+            shutdown_timeout: l.or("server.shutdown_timeout", &["SHUTDOWN_TIMEOUT"], 30),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Returns `Some(None)` when TLS is off, and `None` when it is incomplete.
+    #[expect(clippy::option_option)]
+    fn read(l: &mut Loader) -> Option<Option<Self>> {
+        let Some(cert) = l.optional("tls.cert", &["TLS_CERT"]) else {
+            l.ignore(&["tls.key", "tls.redirect_port"]);
+            return Some(None);
+        };
+        let key = l.required("tls.key", &["TLS_KEY"]);
+        let redirect_port = l.optional("tls.redirect_port", &["TLS_REDIRECT_PORT"]);
+        Some(Some(Self {
+            cert,
+            key: key?,
+            redirect_port,
+        }))
+    }
+}
+
+impl DatabaseConfig {
+    fn read(l: &mut Loader) -> Option<Self> {
+        // the prefixes of the MySQL and MariaDB images, and of the hosting platform
+        let env = |name: &str| {
+            ["MYSQL_", "MARIADB_", "NS_MARIADB_"].map(|prefix| format!("{prefix}{name}"))
+        };
+        let hostname = l.required("database.hostname", &env("HOSTNAME"));
+        let port = l.or("database.port", &env("PORT"), 3306);
+        let user = l.required("database.user", &env("USER"));
+        let password = l.required_secret("database.password", &env("PASSWORD"));
+        let database = l.required("database.database", &env("DATABASE"));
+        let auto_migrate = l.or("database.auto_migrate", &["AUTO_MIGRATE"], true);
+        Some(Self {
+            hostname: hostname?,
+            port,
+            user: user?,
+            password: password?,
+            database: database?,
+            auto_migrate,
+        })
+    }
+}
+
+impl JwtConfig {
+    fn read(l: &mut Loader) -> Option<Self> {
+        let issuer = l.or("jwt.issuer", &["JWT_ISSUER"], "login-with-axum".to_string());
+        let key = l.required_secret("jwt.key", &["JWT_KEY"]);
+        let lifetime = l.or("jwt.lifetime", &["JWT_LIFETIME"], 24 * 60 * 60);
+        Some(Self {
+            issuer,
+            key: key?,
+            lifetime,
+        })
+    }
+}
+
+impl PasswordConfig {
+    fn read(l: &mut Loader) -> Self {
+        let hashing_workers = l.or(
+            "password.hashing_workers",
+            &["HASHING_WORKERS"],
+            std::thread::available_parallelism().map_or(1, usize::from),
+        );
+        Self {
+            algorithm: l.or(
+                "password.algorithm",
+                &["PASSWORD_ALGORITHM"],
+                Algorithm::default(),
+            ),
+            bcrypt_cost: l.or(
+                "password.bcrypt_cost",
+                &["BCRYPT_COST"],
+                bcrypt::DEFAULT_COST,
+            ),
+            argon2_memory_kib: l.or(
+                "password.argon2_memory_kib",
+                &["ARGON2_MEMORY_KIB"],
+                argon2::Params::DEFAULT_M_COST,
+            ),
+            argon2_iterations: l.or(
+                "password.argon2_iterations",
+                &["ARGON2_ITERATIONS"],
+                argon2::Params::DEFAULT_T_COST,
+            ),
+            argon2_parallelism: l.or(
+                "password.argon2_parallelism",
+                &["ARGON2_PARALLELISM"],
+                argon2::Params::DEFAULT_P_COST,
+            ),
+            hashing_workers,
+            hashing_queue_depth: l.or(
+                "password.hashing_queue_depth",
+                &["HASHING_QUEUE_DEPTH"],
+                hashing_workers * 16,
+            ),
+            common_passwords: l
+                .optional("password.common_passwords", &["PASSWORD_COMMON_PASSWORDS"]),
+            range_dir: l.optional("password.range_dir", &["PASSWORD_RANGE_DIR"]),
+            min_breach_count: l.or(
+                "password.min_breach_count",
+                &["PASSWORD_MIN_BREACH_COUNT"],
+                1,
+            ),
+        }
+    }
+}
+
+impl CookieConfig {
+    fn read(l: &mut Loader) -> Self {
+        Self {
+            name: l.or("cookie.name", &["COOKIE_NAME"], "ax_session".to_string()),
+            secure: l.or("cookie.secure", &["COOKIE_SECURE"], true),
+            same_site: l.or(
+                "cookie.same_site",
+                &["COOKIE_SAME_SITE"],
+                SameSite::default(),
+            ),
+            domain: l.optional("cookie.domain", &["COOKIE_DOMAIN"]),
+            host_prefix: l.or("cookie.host_prefix", &["COOKIE_HOST_PREFIX"], false),
+        }
+    }
+}
+
+impl CsrfConfig {
+    fn read(l: &mut Loader) -> Self {
+        let trusted_origins = l
+            .list("csrf.trusted_origins", "CSRF_TRUSTED_ORIGINS")
+            .into_iter()
+            .map(|o| o.trim_end_matches('/').to_string())
+            .collect();
+        Self { trusted_origins }
+    }
+}
+
+impl CorsConfig {
+    fn read(l: &mut Loader) -> Self {
+        let defaults = crate::CorsPolicy::default();
+        let or_defaults = |list: Vec<String>, defaults: Vec<String>| {
+            if list.is_empty() { defaults } else { list }
+        };
+        let methods = or_defaults(
+            l.list("cors.methods", "CORS_METHODS"),
+            defaults.methods.iter().map(ToString::to_string).collect(),
+        );
+        let headers = or_defaults(
+            l.list("cors.headers", "CORS_HEADERS"),
+            defaults.headers.iter().map(ToString::to_string).collect(),
+        );
+        Self {
+            origins: l.list("cors.origins", "CORS_ORIGINS"),
+            credentials: l.or(
+                "cors.credentials",
+                &["CORS_CREDENTIALS"],
+                defaults.credentials,
+            ),
+            methods,
+            headers,
+            max_age: l.or(
+                "cors.max_age",
+                &["CORS_MAX_AGE"],
+                defaults.max_age.as_secs(),
+            ),
+        }
+    }
+}
+
+impl SecurityConfig {
+    fn read(l: &mut Loader) -> Self {
+        let defaults = crate::SecurityHeaders::default();
+        Self {
+            content_security_policy: l.or(
+                "security.content_security_policy",
+                &["SECURITY_CSP"],
+                defaults.content_security_policy,
+            ),
+            frame_ancestors: l.or(
+                "security.frame_ancestors",
+                &["SECURITY_FRAME_ANCESTORS"],
+                defaults.frame_ancestors,
+            ),
+            strict_transport_security: l.or(
+                "security.strict_transport_security",
+                &["SECURITY_HSTS"],
+                defaults.strict_transport_security,
+            ),
+            nosniff: l.or("security.nosniff", &["SECURITY_NOSNIFF"], defaults.nosniff),
+            referrer_policy: l.or(
+                "security.referrer_policy",
+                &["SECURITY_REFERRER_POLICY"],
+                defaults.referrer_policy,
+            ),
+            cache_control: l.or(
+                "security.cache_control",
+                &["SECURITY_CACHE_CONTROL"],
+                defaults.cache_control,
+            ),
+            routes: l.file_only("security.routes").unwrap_or_default(),
+        }
+    }
+}
+
+impl AdminConfig {
+    /// Returns `Some(None)` when no admin is configured, and `None` when it is incomplete.
+    #[expect(clippy::option_option)]
+    fn read(l: &mut Loader) -> Option<Option<Self>> {
+        let Some(display_id) = l.optional::<String, _>("admin.display_id", &["ADMIN_DISPLAY_ID"])
+        else {
+            l.ignore(&["admin.name", "admin.password", "admin.password_file"]);
+            return Some(None);
+        };
+        let name = l.or("admin.name", &["ADMIN_NAME"], display_id.clone());
+        let password = l.required_secret("admin.password", &["ADMIN_PASSWORD"])?;
+        Some(Some(Self {
+            display_id,
+            name,
+            password,
+        }))
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `path`, or [`FILE_ENV`] if unset, and the environment.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut problems = Vec::new();
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(FILE_ENV).map(PathBuf::from));
+        let file = match &path {
+            Some(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))
+                .and_then(|s| {
+                    toml::from_str(&s)
+                        .with_context(|| format!("Failed to parse {}", path.display()))
+                })
+                .unwrap_or_else(|e| {
+                    problems.push(format!("{e:#}"));
+                    toml::Table::new()
+                }),
+            None => toml::Table::new(),
+        };
+        let mut loader = Loader::new(file, problems);
+        let config = Self::read(&mut loader);
+        loader.check_unknown_keys();
+        Self::validate(&mut loader, config.as_ref());
+        match config {
+            Some(config) if loader.problems.is_empty() => Ok(config),
+            _ => Err(ConfigError {
+                problems: loader.problems,
+            }),
+        }
+    }
+
+    fn read(l: &mut Loader) -> Option<Self> {
+        // read every section before bailing out, so that all problems get reported
+        let server = ServerConfig::read(l);
+        let tls = TlsConfig::read(l);
+        let proxy = ProxyConfig {
+            trusted: l.list("proxy.trusted", "TRUSTED_PROXIES"),
+            protocol: l.or("proxy.protocol", &["PROXY_PROTOCOL"], false),
+            trust_unix_socket: l.or("proxy.trust_unix_socket", &["TRUST_UNIX_SOCKET"], false),
+        };
+        let assets = {
+            let defaults = crate::Assets::default();
+            AssetsConfig {
+                dir: l.or("assets.dir", &["ASSETS_DIR"], defaults.dir),
+                embedded: l.or("assets.embedded", &["ASSETS_EMBEDDED"], defaults.embedded),
+                spa_fallback: l.or(
+                    "assets.spa_fallback",
+                    &["ASSETS_SPA_FALLBACK"],
+                    defaults.spa_fallback,
+                ),
+            }
+        };
+        let database = DatabaseConfig::read(l);
+        let jwt = JwtConfig::read(l);
+        let password = PasswordConfig::read(l);
+        let cookie = CookieConfig::read(l);
+        let csrf = CsrfConfig::read(l);
+        let cors = CorsConfig::read(l);
+        let security = SecurityConfig::read(l);
+        let audit = AuditConfig {
+            backend: l.or("audit.backend", &["AUDIT_LOG"], AuditBackend::default()),
+        };
+        let purge = PurgeConfig {
+            retention: l.or(
+                "purge.retention",
+                &["DELETED_USER_RETENTION"],
+                30 * 24 * 60 * 60,
+            ),
+            interval: l.or("purge.interval", &["PURGE_INTERVAL"], 60 * 60),
+        };
+        let telemetry = TelemetryConfig {
+            log_format: l.or(
+                "telemetry.log_format",
+                &["LOG_FORMAT"],
+                LogFormat::default(),
+            ),
+            otlp_endpoint: l.optional("telemetry.otlp_endpoint", &["OTEL_EXPORTER_OTLP_ENDPOINT"]),
+            service_name: l.or(
+                "telemetry.service_name",
+                &["OTEL_SERVICE_NAME"],
+                env!("CARGO_PKG_NAME").to_string(),
+            ),
+        };
+        let admin = AdminConfig::read(l);
+        Some(Self {
+            server,
+            tls: tls?,
+            proxy,
+            assets,
+            database: database?,
+            jwt: jwt?,
+            password,
+            cookie,
+            csrf,
+            cors,
+            security,
+            audit,
+            purge,
+            telemetry,
+            admin: admin?,
+        })
+    }
+
+    fn validate(l: &mut Loader, config: Option<&Self>) {
+        let Some(config) = config else {
+            return;
+        };
+        l.check(!config.jwt.key.as_str().is_empty(), || {
+            "jwt.key must not be empty".into()
+        });
+        let server = &config.server;
+        for (key, value) in [
+            ("server.request_timeout", server.request_timeout),
+            ("server.hashing_timeout", server.hashing_timeout),
+            ("server.body_limit", server.body_limit as u64),
+            ("server.concurrency_limit", server.concurrency_limit as u64),
+        ] {
+            l.check(value > 0, || format!("{key} must be positive"));
+        }
+        l.check(config.jwt.lifetime > 0, || {
+            "jwt.lifetime must be positive".into()
+        });
+        l.check(config.password.hashing_workers > 0, || {
+            "password.hashing_workers must be positive".into()
+        });
+        l.check(config.purge.interval > 0, || {
+            "purge.interval must be positive".into()
+        });
+        if let Err(e) = config.argon2_params() {
+            l.problems.push(format!("password.argon2_*: {e}"));
+        }
+        l.check((4..=31).contains(&config.password.bcrypt_cost), || {
+            "password.bcrypt_cost must be between 4 and 31".into()
+        });
+        if let Some(tls) = &config.tls {
+            l.check(matches!(server.address, BindAddress::Ip(_)), || {
+                "tls needs server.address to be an IP address".into()
+            });
+            l.check(tls.redirect_port != Some(server.port), || {
+                "tls.redirect_port must differ from server.port".into()
+            });
+        }
+        for proxy in &config.proxy.trusted {
+            if let Err(e) = proxy.parse::<crate::proxy::IpNet>() {
+                l.problems.push(format!("proxy.trusted: {proxy}: {e}"));
+            }
+        }
+        l.check(
+            !config.proxy.protocol || !config.proxy.trusted.is_empty(),
+            || "proxy.protocol needs proxy.trusted to list the proxies".into(),
+        );
+        l.check(
+            !config.proxy.protocol || matches!(server.address, BindAddress::Ip(_)),
+            || "proxy.protocol needs server.address to be an IP address".into(),
+        );
+        l.check(
+            !config.proxy.trust_unix_socket || matches!(server.address, BindAddress::Unix(_)),
+            || "proxy.trust_unix_socket needs server.address to be unix:<path>".into(),
+        );
+        l.check(
+            !config.assets.embedded || cfg!(feature = "embed-assets"),
+            || "assets.embedded needs the embed-assets feature".into(),
+        );
+        if let Err(e) = config.cookie_policy().validate() {
+            l.problems.push(format!("cookie: {e}"));
+        }
+        let cors = &config.cors;
+        for origin in &cors.origins {
+            if let Err(e) = origin.parse::<crate::OriginPattern>() {
+                l.problems.push(format!("cors.origins: {origin}: {e}"));
+            }
+        }
+        for method in &cors.methods {
+            l.check(method.parse::<axum::http::Method>().is_ok(), || {
+                format!("cors.methods: {method} is not a method")
+            });
+        }
+        for header in &cors.headers {
+            l.check(header.parse::<axum::http::HeaderName>().is_ok(), || {
+                format!("cors.headers: {header} is not a header name")
+            });
+        }
+        if let Err(e) = config.cors().validate() {
+            l.problems.push(format!("cors: {e}"));
+        }
+        if let Err(e) = config.security().validate() {
+            l.problems.push(format!("security: {e}"));
+        }
+        for origin in &config.csrf.trusted_origins {
+            l.check(origin.contains("://"), || {
+                format!("csrf.trusted_origins: {origin} is not an origin like https://example.com")
+            });
+        }
+    }
+
+    /// Renders the configuration as TOML with secrets redacted.
+    pub fn to_redacted_toml(&self) -> anyhow::Result<String> {
+        toml::to_string_pretty(self).context("Failed to render configuration")
+    }
+
+    pub async fn connect_database(&self) -> anyhow::Result<sqlx::MySqlPool> {
+        let DatabaseConfig {
+            hostname,
+            port,
+            user,
+            password,
+            database,
+            ..
+        } = &self.database;
+        let options = sqlx::mysql::MySqlConnectOptions::new()
+            .host(hostname)
+            .port(*port)
+            .username(user)
+            .password(password.as_str())
+            .database(database);
+        sqlx::MySqlPool::connect_with(options)
+            .await
+            .context("Failed to connect database")
+    }
+
+    #[must_use]
+    pub fn jwt_lifetime(&self) -> Duration {
+        Duration::from_secs(self.jwt.lifetime)
+    }
+
+    pub fn jwt(&self) -> crate::token::Jwt {
+        crate::token::Jwt::builder()
+            .issuer(&self.jwt.issuer)
+            .key(self.jwt.key.as_str())
+            .lifetime(self.jwt_lifetime())
+            .build()
+    }
+
+    pub fn argon2_params(&self) -> anyhow::Result<argon2::Params> {
+        let p = &self.password;
+        argon2::Params::new(
+            p.argon2_memory_kib,
+            p.argon2_iterations,
+            p.argon2_parallelism,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid argon2 params: {e}"))
+    }
+
+    pub fn repository(&self) -> anyhow::Result<crate::Repository> {
+        let p = &self.password;
+        let hasher = crate::password::PasswordHasher::new(p.algorithm)
+            .bcrypt_cost(p.bcrypt_cost)
+            .argon2_params(self.argon2_params()?);
+        let pool =
+            crate::password::HashingPool::new(hasher, p.hashing_workers, p.hashing_queue_depth)?;
+        Ok(crate::Repository::new(pool))
+    }
+
+    pub fn password_policy(&self) -> anyhow::Result<crate::password::Policy> {
+        let mut policy =
+            crate::password::Policy::new().min_breach_count(self.password.min_breach_count);
+        if let Some(path) = &self.password.common_passwords {
+            policy = policy.common_passwords_file(path)?;
+        }
+        if let Some(dir) = &self.password.range_dir {
+            policy = policy.range_dir(dir);
+        }
+        Ok(policy)
+    }
+
+    /// Builds the cookie policy, expiring cookies together with the JWT they carry.
+    pub fn cookie_policy(&self) -> crate::CookiePolicy {
+        use axum_extra::extract::cookie;
+
+        let c = &self.cookie;
+        crate::CookiePolicy {
+            secure: c.secure,
+            same_site: match c.same_site {
+                SameSite::Strict => cookie::SameSite::Strict,
+                SameSite::Lax => cookie::SameSite::Lax,
+                SameSite::None => cookie::SameSite::None,
+            },
+            domain: c.domain.clone(),
+            max_age: Some(self.jwt_lifetime()),
+            host_prefix: c.host_prefix,
+        }
+    }
+
+    /// Builds the CORS policy, skipping entries that fail validation.
+    pub fn cors(&self) -> crate::CorsPolicy {
+        let c = &self.cors;
+        crate::CorsPolicy {
+            origins: c.origins.iter().filter_map(|o| o.parse().ok()).collect(),
+            credentials: c.credentials,
+            methods: c.methods.iter().filter_map(|m| m.parse().ok()).collect(),
+            headers: c.headers.iter().filter_map(|h| h.parse().ok()).collect(),
+            max_age: Duration::from_secs(c.max_age),
+        }
+    }
+
+    /// Builds the security header policy, with the configured routes after the built-in ones.
+    pub fn security(&self) -> crate::SecurityPolicy {
+        let s = &self.security;
+        let mut policy = crate::SecurityPolicy {
+            headers: crate::SecurityHeaders {
+                content_security_policy: s.content_security_policy.clone(),
+                frame_ancestors: s.frame_ancestors.clone(),
+                strict_transport_security: s.strict_transport_security.clone(),
+                nosniff: s.nosniff,
+                referrer_policy: s.referrer_policy.clone(),
+                cache_control: s.cache_control.clone(),
+            },
+            ..crate::SecurityPolicy::default()
+        };
+        policy
+            .routes
+            .extend(s.routes.iter().map(|r| crate::RouteHeaders {
+                path: r.path.clone(),
+                content_security_policy: r.content_security_policy.clone(),
+                frame_ancestors: r.frame_ancestors.clone(),
+                strict_transport_security: r.strict_transport_security.clone(),
+                nosniff: r.nosniff,
+                referrer_policy: r.referrer_policy.clone(),
+                cache_control: r.cache_control.clone(),
+            }));
+        policy
+    }
+
+    pub fn trusted_proxies(&self) -> crate::proxy::TrustedProxies {
+        let nets = self.proxy.trusted.iter().filter_map(|p| p.parse().ok());
+        crate::proxy::TrustedProxies::new(nets.collect())
+            .trust_unix_socket(self.proxy.trust_unix_socket)
+    }
+
+    pub fn assets(&self) -> crate::Assets {
+        let a = &self.assets;
+        crate::Assets {
+            dir: a.dir.clone(),
+            embedded: a.embedded,
+            spa_fallback: a.spa_fallback,
+        }
+    }
+
+    pub fn audit_log(&self, repo: &crate::Repository) -> crate::audit::AuditLogger {
+        match self.audit.backend {
+            AuditBackend::Database => crate::audit::AuditLogger::Database(repo.clone()),
+            AuditBackend::Tracing => {
+                crate::audit::AuditLogger::Tracing(crate::audit::TracingAuditLog)
+            }
+        }
+    }
+
+    pub fn limits(&self) -> crate::Limits {
+        let s = &self.server;
+        crate::Limits {
+            request_timeout: Duration::from_secs(s.request_timeout),
+            hashing_timeout: Duration::from_secs(s.hashing_timeout),
+            body_limit: s.body_limit,
+            concurrency_limit: s.concurrency_limit,
+        }
+    }
+
+    #[must_use]
+    pub fn drain_delay(&self) -> Duration {
+        Duration::from_secs(self.server.drain_delay)
+    }
+
+    #[must_use]
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.shutdown_timeout)
+    }
+
+    /// Returns how long deleted users are retained, and how often they are purged.
+    #[must_use]
+    pub fn purge_schedule(&self) -> (Duration, Duration) {
+        (
+            Duration::from_secs(self.purge.retention),
+            Duration::from_secs(self.purge.interval),
+        )
+    }
+
+    pub fn state_init(&self, pool: sqlx::MySqlPool) -> anyhow::Result<crate::StateInit> {
+        let repo = self.repository()?;
+        let bootstrap_admin = self.admin.as_ref().map(|a| crate::BootstrapAdmin {
+            display_id: a.display_id.clone(),
+            name: a.name.clone(),
+            raw_password: a.password.clone(),
+        });
+        Ok(crate::StateInit {
+            cookie_name: self.cookie.name.clone(),
+            path_prefix: self.server.path_prefix.clone(),
+            cookie_policy: self.cookie_policy(),
+            trusted_origins: self.csrf.trusted_origins.clone(),
+            cors: self.cors(),
+            limits: self.limits(),
+            trusted_proxies: self.trusted_proxies(),
+            assets: self.assets(),
+            security: self.security(),
+            pool,
+            jwt: self.jwt(),
+            audit_log: self.audit_log(&repo),
+            password_policy: self.password_policy()?,
+            repo,
+            auto_migrate: self.database.auto_migrate,
+            bootstrap_admin,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader(file: &str) -> Loader {
+        Loader::new(toml::from_str(file).unwrap(), Vec::new())
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_a_suggestion() {
+        let mut l = loader("[server]\nport = 80\nprot = 81\n[servr]\naddress = \"::\"\n");
+        let _: Option<u16> = l.optional("server.port", &["LOGIN_WITH_AXUM_TEST_UNSET"]);
+        let _: Option<String> = l.optional("server.address", &["LOGIN_WITH_AXUM_TEST_UNSET"]);
+        l.check_unknown_keys();
+        l.problems.sort();
+        assert_eq!(
+            l.problems,
+            [
+                "server.prot: unknown key, did you mean server.port?",
+                "servr.address: unknown key, did you mean server.address?",
+            ]
+        );
+    }
+
+    #[test]
+    fn problems_are_matched_by_exact_key() {
+        let mut l = loader("[a]\nname_extra = \"x\"\n");
+        let _: Option<u16> = l.optional("a.name_extra", &["LOGIN_WITH_AXUM_TEST_UNSET"]);
+        let _: Option<String> = l.required("a.name", &["LOGIN_WITH_AXUM_TEST_UNSET"]);
+        assert_eq!(l.problems.len(), 2, "{:?}", l.problems);
+        assert!(l.problems[1].starts_with("a.name is required"));
+    }
+
+    #[test]
+    fn unreadable_secret_file_is_not_also_missing() {
+        let mut l = loader("[a]\nsecret_file = \"/nonexistent/secret\"\n");
+        let secret = l.required_secret("a.secret", &["LOGIN_WITH_AXUM_TEST_UNSET"]);
+        assert!(secret.is_none());
+        assert_eq!(l.problems.len(), 1, "{:?}", l.problems);
+        assert!(l.problems[0].starts_with("a.secret_file: Failed to read"));
+        l.check_unknown_keys();
+        assert_eq!(l.problems.len(), 1, "{:?}", l.problems);
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("port", "port"), 0);
+        assert_eq!(edit_distance("prot", "port"), 2);
+        assert_eq!(edit_distance("portt", "port"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -1,5 +1,7 @@
+use anyhow::Context as _;
 use serde::{Deserialize, Serialize};
 
+use crate::Secret;
 use crate::error::Failure;
 
 #[must_use]
@@ -12,6 +14,143 @@ pub struct User {
     pub id: UserId,
     pub display_id: String,
     pub name: String,
+    pub status: UserStatus,
+    pub status_updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl User {
+    /// The longest `display_id` or `name`, in characters, that the columns hold.
+    pub const MAX_LEN: usize = 32;
+
+    /// Accepts ASCII letters, digits, `_`, `-` and `.`, up to [`User::MAX_LEN`] of them.
+    pub fn check_display_id(display_id: &str) -> Result<(), Failure> {
+        if display_id.is_empty() || display_id.chars().count() > Self::MAX_LEN {
+            return Err(Failure::bad_request(format!(
+                "Display id must be 1 to {} characters long",
+                Self::MAX_LEN
+            )));
+        }
+        if !display_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return Err(Failure::bad_request(
+                "Display id may only contain letters, digits, '_', '-' and '.'",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Accepts any non-blank text without control characters, up to [`User::MAX_LEN`].
+    pub fn check_name(name: &str) -> Result<(), Failure> {
+        if name.trim().is_empty() || name.chars().count() > Self::MAX_LEN {
+            return Err(Failure::bad_request(format!(
+                "Name must be 1 to {} characters long",
+                Self::MAX_LEN
+            )));
+        }
+        if name.chars().any(char::is_control) {
+            return Err(Failure::bad_request(
+                "Name may not contain control characters",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserStatus {
+    #[default]
+    Active,
+    PendingVerification,
+    Suspended,
+    /// Soft-deleted; the `display_id` stays reserved until the user is purged.
+    Deleted,
+}
+
+impl UserStatus {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::PendingVerification => "pending_verification",
+            Self::Suspended => "suspended",
+            Self::Deleted => "deleted",
+        }
+    }
+
+    /// Rejects every status but [`UserStatus::Active`].
+    pub fn ensure_active(self) -> Result<(), Failure> {
+        match self {
+            Self::Active => Ok(()),
+            Self::PendingVerification => Err(Failure::forbidden("Account is pending verification")),
+            Self::Suspended => Err(Failure::forbidden("Account is suspended")),
+            Self::Deleted => Err(Failure::unauthorized("Account has been deleted")),
+        }
+    }
+}
+
+impl std::str::FromStr for UserStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "pending_verification" => Ok(Self::PendingVerification),
+            "suspended" => Ok(Self::Suspended),
+            "deleted" => Ok(Self::Deleted),
+            _ => Err(anyhow::anyhow!("Unknown user status {s}")),
+        }
+    }
+}
+
+/// A permission string such as `users:write`.
+///
+/// A trailing `*` grants every permission sharing the prefix, so `users:*` grants `users:write`
+/// and `*` grants everything.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Permission(pub String);
+
+impl Permission {
+    #[must_use]
+    pub fn grants(&self, required: &str) -> bool {
+        match self.0.strip_suffix('*') {
+            Some(prefix) => required.starts_with(prefix),
+            None => self.0 == required,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Role {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+}
+
+impl Role {
+    pub const ADMIN: &str = "admin";
+}
+
+/// The authenticated subject of a [`Credential`].
+///
+/// Roles and permissions are captured when the credential is made, so changes take effect on the
+/// next login.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Principal {
+    pub user_id: UserId,
+    pub session_id: SessionId,
+    pub roles: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+impl Principal {
+    #[must_use]
+    pub fn has_permission(&self, required: &str) -> bool {
+        self.permissions.iter().any(|p| p.grants(required))
+    }
 }
 
 // MARK: UserRepository
@@ -24,34 +163,190 @@ pub enum GetUserParams {
     ByDisplayId(String),
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserOrderBy {
+    #[default]
+    DisplayId,
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GetUsersParams {
+    /// Prefix matched against `display_id` or `name`.
+    pub search: Option<String>,
+    pub order_by: UserOrderBy,
+    pub order: SortOrder,
+    pub limit: u32,
+    /// The [`UserPage::next_cursor`] of the previous page, with the same `order_by`.
+    pub after: Option<UserCursor>,
+    /// List soft-deleted users too.
+    pub include_deleted: bool,
+}
+
+impl GetUsersParams {
+    pub const DEFAULT_LIMIT: u32 = 50;
+    pub const MAX_LIMIT: u32 = 200;
+}
+
+impl Default for GetUsersParams {
+    fn default() -> Self {
+        Self {
+            search: None,
+            order_by: UserOrderBy::default(),
+            order: SortOrder::default(),
+            limit: Self::DEFAULT_LIMIT,
+            after: None,
+            include_deleted: false,
+        }
+    }
+}
+
+/// An opaque position in a user listing: the sort key and `display_id` of the last user of a
+/// page, so that it stays valid when that user is renamed or deleted.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UserCursor(pub String);
+
+/// What a [`UserCursor`] encodes, as base64url JSON.
+#[derive(Deserialize, Serialize)]
+struct UserCursorFields {
+    order_by: UserOrderBy,
+    key: String,
+    display_id: String,
+}
+
+impl UserCursor {
+    pub fn new(order_by: UserOrderBy, key: &str, display_id: &str) -> anyhow::Result<Self> {
+        use base64::Engine as _;
+
+        let fields = UserCursorFields {
+            order_by,
+            key: key.to_string(),
+            display_id: display_id.to_string(),
+        };
+        let json = serde_json::to_vec(&fields).context("Failed to encode cursor")?;
+        Ok(Self(
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json),
+        ))
+    }
+
+    /// Returns the sort key and `display_id`, if the cursor was made for `order_by`.
+    pub fn decode(&self, order_by: UserOrderBy) -> Result<(String, String), Failure> {
+        use base64::Engine as _;
+
+        let invalid = || Failure::bad_request("Invalid cursor");
+        let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(&self.0)
+            .map_err(|_| invalid())?;
+        let fields: UserCursorFields = serde_json::from_slice(&json).map_err(|_| invalid())?;
+        if fields.order_by != order_by {
+            return Err(invalid());
+        }
+        Ok((fields.key, fields.display_id))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UserPage {
+    pub users: Vec<User>,
+    /// Users matching the search, counted for the first page only and up to
+    /// [`UserPage::COUNT_LIMIT`].
+    pub total: Option<u64>,
+    pub next_cursor: Option<UserCursor>,
+}
+
+impl UserPage {
+    pub const COUNT_LIMIT: u64 = 10_000;
+}
+
 #[must_use]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct CreateUserParams {
     pub display_id: String,
     pub name: String,
+    pub raw_password: Secret,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UpdateUserParams {
+    pub id: UserId,
+    pub display_id: Option<String>,
+    pub name: Option<String>,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct SetUserStatusParams {
+    pub id: UserId,
+    pub status: UserStatus,
 }
 
 #[must_use]
 pub trait UserRepository<Context>: Send + Sync {
-    fn get_users(&self, ctx: Context) -> impl Future<Output = Result<Vec<User>, Failure>> + Send;
+    fn get_users(
+        &self,
+        ctx: Context,
+        params: GetUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send;
     fn get_user(
         &self,
         ctx: Context,
         params: GetUserParams,
     ) -> impl Future<Output = Result<User, Failure>> + Send;
+    /// Hashes the password before storing the user together with it, so that a failed hash
+    /// leaves nothing behind.
     fn create_user(
         &self,
         ctx: Context,
         params: CreateUserParams,
     ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn update_user(
+        &self,
+        ctx: Context,
+        params: UpdateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn set_user_status(
+        &self,
+        ctx: Context,
+        params: SetUserStatusParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    /// Marks the user as [`UserStatus::Deleted`] and drops its password, role grants and sessions.
+    fn delete_user(
+        &self,
+        ctx: Context,
+        id: UserId,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    /// Hard-deletes users deleted before `deleted_before`, returning how many were purged.
+    fn purge_deleted_users(
+        &self,
+        ctx: Context,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send;
 }
 
 impl<T, C> UserRepository<C> for &T
 where
     T: UserRepository<C>,
 {
-    fn get_users(&self, ctx: C) -> impl Future<Output = Result<Vec<User>, Failure>> + Send {
-        T::get_users(self, ctx)
+    fn get_users(
+        &self,
+        ctx: C,
+        params: GetUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send {
+        T::get_users(self, ctx, params)
     }
     fn get_user(
         &self,
@@ -67,6 +362,30 @@ where
     ) -> impl Future<Output = Result<User, Failure>> + Send {
         T::create_user(self, ctx, params)
     }
+    fn update_user(
+        &self,
+        ctx: C,
+        params: UpdateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::update_user(self, ctx, params)
+    }
+    fn set_user_status(
+        &self,
+        ctx: C,
+        params: SetUserStatusParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::set_user_status(self, ctx, params)
+    }
+    fn delete_user(&self, ctx: C, id: UserId) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::delete_user(self, ctx, id)
+    }
+    fn purge_deleted_users(
+        &self,
+        ctx: C,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        T::purge_deleted_users(self, ctx, deleted_before)
+    }
 }
 
 #[must_use]
@@ -81,9 +400,12 @@ pub trait ProvideUserRepository: Send + Sync {
     fn context(&self) -> Self::Context<'_>;
     fn user_repository(&self) -> &Self::UserRepository<'_>;
 
-    fn get_users(&self) -> impl Future<Output = Result<Vec<User>, Failure>> + Send {
+    fn get_users(
+        &self,
+        params: GetUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send {
         let ctx = self.context();
-        self.user_repository().get_users(ctx)
+        self.user_repository().get_users(ctx, params)
     }
     fn get_user(
         &self,
@@ -99,6 +421,32 @@ pub trait ProvideUserRepository: Send + Sync {
         let ctx = self.context();
         self.user_repository().create_user(ctx, params)
     }
+    fn update_user(
+        &self,
+        params: UpdateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        let ctx = self.context();
+        self.user_repository().update_user(ctx, params)
+    }
+    fn set_user_status(
+        &self,
+        params: SetUserStatusParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        let ctx = self.context();
+        self.user_repository().set_user_status(ctx, params)
+    }
+    fn delete_user(&self, id: UserId) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.user_repository().delete_user(ctx, id)
+    }
+    fn purge_deleted_users(
+        &self,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        let ctx = self.context();
+        self.user_repository()
+            .purge_deleted_users(ctx, deleted_before)
+    }
 }
 
 impl<T> ProvideUserRepository for &T
@@ -127,13 +475,13 @@ where
 #[must_use]
 pub struct SaveUserPasswordParams {
     pub user_id: UserId,
-    pub raw: String,
+    pub raw: Secret,
 }
 
 #[must_use]
 pub struct VerifyUserPasswordParams {
     pub user_id: UserId,
-    pub raw: String,
+    pub raw: Secret,
 }
 
 #[must_use]
@@ -221,222 +569,1052 @@ where
     }
 }
 
-// MARK: CredentialManager
+// MARK: RoleRepository
 
 #[must_use]
-#[derive(Clone, PartialEq, Eq, Hash)]
-pub struct Credential(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct CreateRoleParams {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+}
 
 #[must_use]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
-pub struct MakeCredentialParams {
+pub struct GrantRoleParams {
     pub user_id: UserId,
+    pub role: String,
 }
 
 #[must_use]
-pub trait CredentialManager<Context>: Send + Sync {
-    fn make_credential(
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RevokeRoleParams {
+    pub user_id: UserId,
+    pub role: String,
+}
+
+#[must_use]
+pub trait RoleRepository<Context>: Send + Sync {
+    fn get_roles(&self, ctx: Context) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send;
+    fn get_user_roles(
         &self,
         ctx: Context,
-        params: MakeCredentialParams,
-    ) -> impl Future<Output = Result<Credential, Failure>> + Send;
-    fn revoke_credential(
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send;
+    fn create_role(
         &self,
         ctx: Context,
-        credential: Credential,
+        params: CreateRoleParams,
+    ) -> impl Future<Output = Result<Role, Failure>> + Send;
+    fn grant_role(
+        &self,
+        ctx: Context,
+        params: GrantRoleParams,
     ) -> impl Future<Output = Result<(), Failure>> + Send;
-    fn check_credential(
+    fn revoke_role(
         &self,
         ctx: Context,
-        credential: Credential,
-    ) -> impl Future<Output = Result<UserId, Failure>> + Send;
+        params: RevokeRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
 }
 
-impl<T, C> CredentialManager<C> for &T
+impl<T, C> RoleRepository<C> for &T
 where
-    T: CredentialManager<C>,
+    T: RoleRepository<C>,
 {
-    fn make_credential(
+    fn get_roles(&self, ctx: C) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send {
+        T::get_roles(self, ctx)
+    }
+    fn get_user_roles(
         &self,
         ctx: C,
-        params: MakeCredentialParams,
-    ) -> impl Future<Output = Result<Credential, Failure>> + Send {
-        T::make_credential(self, ctx, params)
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send {
+        T::get_user_roles(self, ctx, user_id)
     }
-    fn revoke_credential(
+    fn create_role(
         &self,
         ctx: C,
-        credential: Credential,
+        params: CreateRoleParams,
+    ) -> impl Future<Output = Result<Role, Failure>> + Send {
+        T::create_role(self, ctx, params)
+    }
+    fn grant_role(
+        &self,
+        ctx: C,
+        params: GrantRoleParams,
     ) -> impl Future<Output = Result<(), Failure>> + Send {
-        T::revoke_credential(self, ctx, credential)
+        T::grant_role(self, ctx, params)
     }
-    fn check_credential(
+    fn revoke_role(
         &self,
         ctx: C,
-        credential: Credential,
-    ) -> impl Future<Output = Result<UserId, Failure>> + Send {
-        T::check_credential(self, ctx, credential)
+        params: RevokeRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::revoke_role(self, ctx, params)
     }
 }
 
 #[must_use]
-pub trait ProvideCredentialManager: Send + Sync {
+pub trait ProvideRoleRepository: Send + Sync {
     type Context<'a>
     where
         Self: 'a;
-    type CredentialManager<'a>: CredentialManager<Self::Context<'a>>
+    type RoleRepository<'a>: RoleRepository<Self::Context<'a>>
     where
         Self: 'a;
 
     fn context(&self) -> Self::Context<'_>;
-    fn credential_manager(&self) -> &Self::CredentialManager<'_>;
+    fn role_repository(&self) -> &Self::RoleRepository<'_>;
 
-    fn make_credential(
+    fn get_roles(&self) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send {
+        let ctx = self.context();
+        self.role_repository().get_roles(ctx)
+    }
+    fn get_user_roles(
         &self,
-        params: MakeCredentialParams,
-    ) -> impl Future<Output = Result<Credential, Failure>> + Send {
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send {
         let ctx = self.context();
-        self.credential_manager().make_credential(ctx, params)
+        self.role_repository().get_user_roles(ctx, user_id)
     }
-    fn revoke_credential(
+    fn create_role(
         &self,
-        credential: Credential,
+        params: CreateRoleParams,
+    ) -> impl Future<Output = Result<Role, Failure>> + Send {
+        let ctx = self.context();
+        self.role_repository().create_role(ctx, params)
+    }
+    fn grant_role(
+        &self,
+        params: GrantRoleParams,
     ) -> impl Future<Output = Result<(), Failure>> + Send {
         let ctx = self.context();
-        self.credential_manager().revoke_credential(ctx, credential)
+        self.role_repository().grant_role(ctx, params)
     }
-    fn check_credential(
+    fn revoke_role(
         &self,
-        credential: Credential,
-    ) -> impl Future<Output = Result<UserId, Failure>> + Send {
+        params: RevokeRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
         let ctx = self.context();
-        self.credential_manager().check_credential(ctx, credential)
+        self.role_repository().revoke_role(ctx, params)
     }
 }
 
-impl<T> ProvideCredentialManager for &T
+impl<T> ProvideRoleRepository for &T
 where
-    T: ProvideCredentialManager,
+    T: ProvideRoleRepository,
 {
     type Context<'a>
         = T::Context<'a>
     where
         Self: 'a;
-    type CredentialManager<'a>
-        = T::CredentialManager<'a>
+    type RoleRepository<'a>
+        = T::RoleRepository<'a>
     where
         Self: 'a;
 
     fn context(&self) -> Self::Context<'_> {
         T::context(self)
     }
-    fn credential_manager(&self) -> &Self::CredentialManager<'_> {
-        T::credential_manager(self)
+    fn role_repository(&self) -> &Self::RoleRepository<'_> {
+        T::role_repository(self)
     }
 }
 
-// MARK: UserRegistry
+// MARK: SessionRepository
 
 #[must_use]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SessionId(pub uuid::Uuid);
+
+/// A signed-in device, backing each [`Credential`].
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
-pub struct RegisterUserParams {
-    pub display_id: String,
-    pub name: String,
-    pub raw_password: String,
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub user_agent: Option<String>,
+    pub ip: Option<std::net::IpAddr>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A session with the status of its user, fetched together on every credential check.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct SessionWithUserStatus {
+    pub session: Session,
+    pub user_status: UserStatus,
 }
 
 #[must_use]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
-pub struct UpdateUserPasswordParams {
+pub struct CreateSessionParams {
     pub user_id: UserId,
-    #[serde(rename = "password")]
-    pub new_raw: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<std::net::IpAddr>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[must_use]
-pub trait UserRegistry<Context>: Send + Sync {
-    fn get_user(
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct TouchSessionParams {
+    pub id: SessionId,
+    pub used_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct DeleteSessionParams {
+    pub user_id: UserId,
+    pub id: SessionId,
+}
+
+#[must_use]
+pub trait SessionRepository<Context>: Send + Sync {
+    fn create_session(
         &self,
         ctx: Context,
-        params: GetUserParams,
-    ) -> impl Future<Output = Result<User, Failure>> + Send;
-    fn get_users(&self, ctx: Context) -> impl Future<Output = Result<Vec<User>, Failure>> + Send;
-    fn register_user(
+        params: CreateSessionParams,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send;
+    fn get_session(
         &self,
         ctx: Context,
-        params: RegisterUserParams,
-    ) -> impl Future<Output = Result<User, Failure>> + Send;
-    fn verify_user_password(
+        id: SessionId,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send;
+    /// Returns the session with the status of its user, in one round trip.
+    fn get_session_with_user_status(
         &self,
         ctx: Context,
-        params: VerifyUserPasswordParams,
-    ) -> impl Future<Output = Result<bool, Failure>> + Send;
-    fn update_user_password(
+        id: SessionId,
+    ) -> impl Future<Output = Result<SessionWithUserStatus, Failure>> + Send;
+    /// Returns the unexpired sessions of the user, most recently used first.
+    fn get_user_sessions(
         &self,
         ctx: Context,
-        params: UpdateUserPasswordParams,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Session>, Failure>> + Send;
+    fn touch_session(
+        &self,
+        ctx: Context,
+        params: TouchSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    fn delete_session(
+        &self,
+        ctx: Context,
+        params: DeleteSessionParams,
     ) -> impl Future<Output = Result<(), Failure>> + Send;
+    /// Deletes every session of the user, returning how many were deleted.
+    fn delete_user_sessions(
+        &self,
+        ctx: Context,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send;
 }
 
-impl<T, C> UserRegistry<C> for &T
+impl<T, C> SessionRepository<C> for &T
 where
-    T: UserRegistry<C>,
+    T: SessionRepository<C>,
 {
-    fn get_user(
+    fn create_session(
         &self,
         ctx: C,
-        params: GetUserParams,
-    ) -> impl Future<Output = Result<User, Failure>> + Send {
-        T::get_user(self, ctx, params)
+        params: CreateSessionParams,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send {
+        T::create_session(self, ctx, params)
     }
-    fn get_users(&self, ctx: C) -> impl Future<Output = Result<Vec<User>, Failure>> + Send {
-        T::get_users(self, ctx)
+    fn get_session(
+        &self,
+        ctx: C,
+        id: SessionId,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send {
+        T::get_session(self, ctx, id)
     }
-    fn register_user(
+    fn get_session_with_user_status(
         &self,
         ctx: C,
-        params: RegisterUserParams,
-    ) -> impl Future<Output = Result<User, Failure>> + Send {
-        T::register_user(self, ctx, params)
+        id: SessionId,
+    ) -> impl Future<Output = Result<SessionWithUserStatus, Failure>> + Send {
+        T::get_session_with_user_status(self, ctx, id)
     }
-    fn verify_user_password(
+    fn get_user_sessions(
         &self,
         ctx: C,
-        params: VerifyUserPasswordParams,
-    ) -> impl Future<Output = Result<bool, Failure>> + Send {
-        T::verify_user_password(self, ctx, params)
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Session>, Failure>> + Send {
+        T::get_user_sessions(self, ctx, user_id)
     }
-    fn update_user_password(
+    fn touch_session(
         &self,
         ctx: C,
-        params: UpdateUserPasswordParams,
+        params: TouchSessionParams,
     ) -> impl Future<Output = Result<(), Failure>> + Send {
-        T::update_user_password(self, ctx, params)
+        T::touch_session(self, ctx, params)
+    }
+    fn delete_session(
+        &self,
+        ctx: C,
+        params: DeleteSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::delete_session(self, ctx, params)
+    }
+    fn delete_user_sessions(
+        &self,
+        ctx: C,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        T::delete_user_sessions(self, ctx, user_id)
     }
 }
 
 #[must_use]
-pub trait ProvideUserRegistry: Send + Sync {
+pub trait ProvideSessionRepository: Send + Sync {
     type Context<'a>
     where
         Self: 'a;
-    type UserRegistry<'a>: UserRegistry<Self::Context<'a>>
+    type SessionRepository<'a>: SessionRepository<Self::Context<'a>>
     where
         Self: 'a;
 
     fn context(&self) -> Self::Context<'_>;
-    fn user_registry(&self) -> &Self::UserRegistry<'_>;
+    fn session_repository(&self) -> &Self::SessionRepository<'_>;
 
-    fn get_user(
+    fn create_session(
         &self,
-        params: GetUserParams,
-    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        params: CreateSessionParams,
+    ) -> impl Future<Output = Result<Session, Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository().create_session(ctx, params)
+    }
+    fn get_session(&self, id: SessionId) -> impl Future<Output = Result<Session, Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository().get_session(ctx, id)
+    }
+    fn get_session_with_user_status(
+        &self,
+        id: SessionId,
+    ) -> impl Future<Output = Result<SessionWithUserStatus, Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository()
+            .get_session_with_user_status(ctx, id)
+    }
+    fn get_user_sessions(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Session>, Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository().get_user_sessions(ctx, user_id)
+    }
+    fn touch_session(
+        &self,
+        params: TouchSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository().touch_session(ctx, params)
+    }
+    fn delete_session(
+        &self,
+        params: DeleteSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository().delete_session(ctx, params)
+    }
+    fn delete_user_sessions(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        let ctx = self.context();
+        self.session_repository().delete_user_sessions(ctx, user_id)
+    }
+}
+
+impl<T> ProvideSessionRepository for &T
+where
+    T: ProvideSessionRepository,
+{
+    type Context<'a>
+        = T::Context<'a>
+    where
+        Self: 'a;
+    type SessionRepository<'a>
+        = T::SessionRepository<'a>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_> {
+        T::context(self)
+    }
+    fn session_repository(&self) -> &Self::SessionRepository<'_> {
+        T::session_repository(self)
+    }
+}
+
+// MARK: CredentialManager
+
+#[must_use]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Credential(pub String);
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct MakeCredentialParams {
+    pub user_id: UserId,
+    pub roles: Vec<Role>,
+    pub user_agent: Option<String>,
+    pub ip: Option<std::net::IpAddr>,
+}
+
+#[must_use]
+pub trait CredentialManager<Context>: Send + Sync {
+    fn make_credential(
+        &self,
+        ctx: Context,
+        params: MakeCredentialParams,
+    ) -> impl Future<Output = Result<Credential, Failure>> + Send;
+    fn revoke_credential(
+        &self,
+        ctx: Context,
+        credential: Credential,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    fn check_credential(
+        &self,
+        ctx: Context,
+        credential: Credential,
+    ) -> impl Future<Output = Result<Principal, Failure>> + Send;
+    fn get_sessions(
+        &self,
+        ctx: Context,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Session>, Failure>> + Send;
+    fn revoke_session(
+        &self,
+        ctx: Context,
+        params: DeleteSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    /// Signs the user out everywhere, returning how many sessions were revoked.
+    fn revoke_all_sessions(
+        &self,
+        ctx: Context,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send;
+}
+
+impl<T, C> CredentialManager<C> for &T
+where
+    T: CredentialManager<C>,
+{
+    fn make_credential(
+        &self,
+        ctx: C,
+        params: MakeCredentialParams,
+    ) -> impl Future<Output = Result<Credential, Failure>> + Send {
+        T::make_credential(self, ctx, params)
+    }
+    fn revoke_credential(
+        &self,
+        ctx: C,
+        credential: Credential,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::revoke_credential(self, ctx, credential)
+    }
+    fn check_credential(
+        &self,
+        ctx: C,
+        credential: Credential,
+    ) -> impl Future<Output = Result<Principal, Failure>> + Send {
+        T::check_credential(self, ctx, credential)
+    }
+    fn get_sessions(
+        &self,
+        ctx: C,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Session>, Failure>> + Send {
+        T::get_sessions(self, ctx, user_id)
+    }
+    fn revoke_session(
+        &self,
+        ctx: C,
+        params: DeleteSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::revoke_session(self, ctx, params)
+    }
+    fn revoke_all_sessions(
+        &self,
+        ctx: C,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        T::revoke_all_sessions(self, ctx, user_id)
+    }
+}
+
+#[must_use]
+pub trait ProvideCredentialManager: Send + Sync {
+    type Context<'a>
+    where
+        Self: 'a;
+    type CredentialManager<'a>: CredentialManager<Self::Context<'a>>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_>;
+    fn credential_manager(&self) -> &Self::CredentialManager<'_>;
+
+    fn make_credential(
+        &self,
+        params: MakeCredentialParams,
+    ) -> impl Future<Output = Result<Credential, Failure>> + Send {
+        let ctx = self.context();
+        self.credential_manager().make_credential(ctx, params)
+    }
+    fn revoke_credential(
+        &self,
+        credential: Credential,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.credential_manager().revoke_credential(ctx, credential)
+    }
+    fn check_credential(
+        &self,
+        credential: Credential,
+    ) -> impl Future<Output = Result<Principal, Failure>> + Send {
+        let ctx = self.context();
+        self.credential_manager().check_credential(ctx, credential)
+    }
+    fn get_sessions(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Session>, Failure>> + Send {
+        let ctx = self.context();
+        self.credential_manager().get_sessions(ctx, user_id)
+    }
+    fn revoke_session(
+        &self,
+        params: DeleteSessionParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.credential_manager().revoke_session(ctx, params)
+    }
+    fn revoke_all_sessions(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        let ctx = self.context();
+        self.credential_manager().revoke_all_sessions(ctx, user_id)
+    }
+}
+
+impl<T> ProvideCredentialManager for &T
+where
+    T: ProvideCredentialManager,
+{
+    type Context<'a>
+        = T::Context<'a>
+    where
+        Self: 'a;
+    type CredentialManager<'a>
+        = T::CredentialManager<'a>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_> {
+        T::context(self)
+    }
+    fn credential_manager(&self) -> &Self::CredentialManager<'_> {
+        T::credential_manager(self)
+    }
+}
+
+// MARK: AuditLog
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    Register,
+    LoginSuccess,
+    LoginFailure,
+    Logout,
+    PasswordChange,
+    AccountUpdate,
+    AccountDelete,
+    AdminAction,
+}
+
+impl AuditEventKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::LoginSuccess => "login_success",
+            Self::LoginFailure => "login_failure",
+            Self::Logout => "logout",
+            Self::PasswordChange => "password_change",
+            Self::AccountUpdate => "account_update",
+            Self::AccountDelete => "account_delete",
+            Self::AdminAction => "admin_action",
+        }
+    }
+}
+
+impl std::str::FromStr for AuditEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "register" => Ok(Self::Register),
+            "login_success" => Ok(Self::LoginSuccess),
+            "login_failure" => Ok(Self::LoginFailure),
+            "logout" => Ok(Self::Logout),
+            "password_change" => Ok(Self::PasswordChange),
+            "account_update" => Ok(Self::AccountUpdate),
+            "account_delete" => Ok(Self::AccountDelete),
+            "admin_action" => Ok(Self::AdminAction),
+            _ => Err(anyhow::anyhow!("Unknown audit event kind {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct AuditEvent {
+    pub id: u64,
+    pub occurred_at: chrono::DateTime<chrono::Utc>,
+    pub kind: AuditEventKind,
+    /// The user performing the action, if known.
+    pub actor: Option<UserId>,
+    /// The user the action was performed on, if other than the actor.
+    pub subject: Option<UserId>,
+    pub detail: Option<String>,
+    pub ip: Option<std::net::IpAddr>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RecordAuditEventParams {
+    pub kind: AuditEventKind,
+    pub actor: Option<UserId>,
+    pub subject: Option<UserId>,
+    pub detail: Option<String>,
+    pub ip: Option<std::net::IpAddr>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SearchAuditEventsParams {
+    /// Matches events where the user is either the actor or the subject.
+    pub user_id: Option<UserId>,
+    pub kind: Option<AuditEventKind>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Returns events older than the one with this id; events are ordered newest first.
+    pub before: Option<u64>,
+    pub limit: u32,
+}
+
+impl SearchAuditEventsParams {
+    pub const DEFAULT_LIMIT: u32 = 50;
+    pub const MAX_LIMIT: u32 = 200;
+}
+
+impl Default for SearchAuditEventsParams {
+    fn default() -> Self {
+        Self {
+            user_id: None,
+            kind: None,
+            since: None,
+            until: None,
+            before: None,
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[must_use]
+pub trait AuditLog<Context>: Send + Sync {
+    fn record_audit_event(
+        &self,
+        ctx: Context,
+        params: RecordAuditEventParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    fn search_audit_events(
+        &self,
+        ctx: Context,
+        params: SearchAuditEventsParams,
+    ) -> impl Future<Output = Result<Vec<AuditEvent>, Failure>> + Send;
+}
+
+impl<T, C> AuditLog<C> for &T
+where
+    T: AuditLog<C>,
+{
+    fn record_audit_event(
+        &self,
+        ctx: C,
+        params: RecordAuditEventParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::record_audit_event(self, ctx, params)
+    }
+    fn search_audit_events(
+        &self,
+        ctx: C,
+        params: SearchAuditEventsParams,
+    ) -> impl Future<Output = Result<Vec<AuditEvent>, Failure>> + Send {
+        T::search_audit_events(self, ctx, params)
+    }
+}
+
+#[must_use]
+pub trait ProvideAuditLog: Send + Sync {
+    type Context<'a>
+    where
+        Self: 'a;
+    type AuditLog<'a>: AuditLog<Self::Context<'a>>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_>;
+    fn audit_log(&self) -> &Self::AuditLog<'_>;
+
+    fn record_audit_event(
+        &self,
+        params: RecordAuditEventParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.audit_log().record_audit_event(ctx, params)
+    }
+    fn search_audit_events(
+        &self,
+        params: SearchAuditEventsParams,
+    ) -> impl Future<Output = Result<Vec<AuditEvent>, Failure>> + Send {
+        let ctx = self.context();
+        self.audit_log().search_audit_events(ctx, params)
+    }
+}
+
+impl<T> ProvideAuditLog for &T
+where
+    T: ProvideAuditLog,
+{
+    type Context<'a>
+        = T::Context<'a>
+    where
+        Self: 'a;
+    type AuditLog<'a>
+        = T::AuditLog<'a>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_> {
+        T::context(self)
+    }
+    fn audit_log(&self) -> &Self::AuditLog<'_> {
+        T::audit_log(self)
+    }
+}
+
+// MARK: PasswordPolicy
+
+#[must_use]
+pub struct CheckPasswordParams {
+    pub raw: Secret,
+}
+
+/// Decides whether a new password is acceptable.
+///
+/// Rejections are [`Failure::bad_request_with_reason`] with a reason code for clients.
+#[must_use]
+pub trait PasswordPolicy<Context>: Send + Sync {
+    fn check_password(
+        &self,
+        ctx: Context,
+        params: CheckPasswordParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+}
+
+impl<T, C> PasswordPolicy<C> for &T
+where
+    T: PasswordPolicy<C>,
+{
+    fn check_password(
+        &self,
+        ctx: C,
+        params: CheckPasswordParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::check_password(self, ctx, params)
+    }
+}
+
+#[must_use]
+pub trait ProvidePasswordPolicy: Send + Sync {
+    type Context<'a>
+    where
+        Self: 'a;
+    type PasswordPolicy<'a>: PasswordPolicy<Self::Context<'a>>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_>;
+    fn password_policy(&self) -> &Self::PasswordPolicy<'_>;
+
+    fn check_password(
+        &self,
+        params: CheckPasswordParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.password_policy().check_password(ctx, params)
+    }
+}
+
+impl<T> ProvidePasswordPolicy for &T
+where
+    T: ProvidePasswordPolicy,
+{
+    type Context<'a>
+        = T::Context<'a>
+    where
+        Self: 'a;
+    type PasswordPolicy<'a>
+        = T::PasswordPolicy<'a>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_> {
+        T::context(self)
+    }
+    fn password_policy(&self) -> &Self::PasswordPolicy<'_> {
+        T::password_policy(self)
+    }
+}
+
+// MARK: UserRegistry
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct RegisterUserParams {
+    pub display_id: String,
+    pub name: String,
+    pub raw_password: Secret,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct UpdateUserPasswordParams {
+    pub user_id: UserId,
+    #[serde(rename = "password")]
+    pub new_raw: Secret,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct DeleteUserParams {
+    pub user_id: UserId,
+    /// Re-confirmation of the current password.
+    pub raw_password: Secret,
+}
+
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct AuthenticateUserParams {
+    pub display_id: String,
+    pub raw_password: Secret,
+}
+
+#[must_use]
+pub trait UserRegistry<Context>: Send + Sync {
+    fn get_user(
+        &self,
+        ctx: Context,
+        params: GetUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn get_users(
+        &self,
+        ctx: Context,
+        params: GetUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send;
+    fn register_user(
+        &self,
+        ctx: Context,
+        params: RegisterUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn update_user(
+        &self,
+        ctx: Context,
+        params: UpdateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn delete_user(
+        &self,
+        ctx: Context,
+        params: DeleteUserParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    fn set_user_status(
+        &self,
+        ctx: Context,
+        params: SetUserStatusParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn purge_deleted_users(
+        &self,
+        ctx: Context,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send;
+    fn verify_user_password(
+        &self,
+        ctx: Context,
+        params: VerifyUserPasswordParams,
+    ) -> impl Future<Output = Result<bool, Failure>> + Send;
+    /// Looks up a user by display id and checks the password, spending the same hashing
+    /// work whether or not the user exists.
+    fn authenticate_user(
+        &self,
+        ctx: Context,
+        params: AuthenticateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send;
+    fn update_user_password(
+        &self,
+        ctx: Context,
+        params: UpdateUserPasswordParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    fn get_user_roles(
+        &self,
+        ctx: Context,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send;
+    fn grant_role(
+        &self,
+        ctx: Context,
+        params: GrantRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+    fn revoke_role(
+        &self,
+        ctx: Context,
+        params: RevokeRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send;
+}
+
+impl<T, C> UserRegistry<C> for &T
+where
+    T: UserRegistry<C>,
+{
+    fn get_user(
+        &self,
+        ctx: C,
+        params: GetUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::get_user(self, ctx, params)
+    }
+    fn get_users(
+        &self,
+        ctx: C,
+        params: GetUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send {
+        T::get_users(self, ctx, params)
+    }
+    fn register_user(
+        &self,
+        ctx: C,
+        params: RegisterUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::register_user(self, ctx, params)
+    }
+    fn update_user(
+        &self,
+        ctx: C,
+        params: UpdateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::update_user(self, ctx, params)
+    }
+    fn delete_user(
+        &self,
+        ctx: C,
+        params: DeleteUserParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::delete_user(self, ctx, params)
+    }
+    fn set_user_status(
+        &self,
+        ctx: C,
+        params: SetUserStatusParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::set_user_status(self, ctx, params)
+    }
+    fn purge_deleted_users(
+        &self,
+        ctx: C,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        T::purge_deleted_users(self, ctx, deleted_before)
+    }
+    fn verify_user_password(
+        &self,
+        ctx: C,
+        params: VerifyUserPasswordParams,
+    ) -> impl Future<Output = Result<bool, Failure>> + Send {
+        T::verify_user_password(self, ctx, params)
+    }
+    fn authenticate_user(
+        &self,
+        ctx: C,
+        params: AuthenticateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        T::authenticate_user(self, ctx, params)
+    }
+    fn update_user_password(
+        &self,
+        ctx: C,
+        params: UpdateUserPasswordParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::update_user_password(self, ctx, params)
+    }
+    fn get_user_roles(
+        &self,
+        ctx: C,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send {
+        T::get_user_roles(self, ctx, user_id)
+    }
+    fn grant_role(
+        &self,
+        ctx: C,
+        params: GrantRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::grant_role(self, ctx, params)
+    }
+    fn revoke_role(
+        &self,
+        ctx: C,
+        params: RevokeRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        T::revoke_role(self, ctx, params)
+    }
+}
+
+#[must_use]
+pub trait ProvideUserRegistry: Send + Sync {
+    type Context<'a>
+    where
+        Self: 'a;
+    type UserRegistry<'a>: UserRegistry<Self::Context<'a>>
+    where
+        Self: 'a;
+
+    fn context(&self) -> Self::Context<'_>;
+    fn user_registry(&self) -> &Self::UserRegistry<'_>;
+
+    fn get_user(
+        &self,
+        params: GetUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
         let ctx = self.context();
         self.user_registry().get_user(ctx, params)
     }
-    fn get_users(&self) -> impl Future<Output = Result<Vec<User>, Failure>> + Send {
+    fn get_users(
+        &self,
+        params: GetUsersParams,
+    ) -> impl Future<Output = Result<UserPage, Failure>> + Send {
         let ctx = self.context();
-        self.user_registry().get_users(ctx)
+        self.user_registry().get_users(ctx, params)
     }
     fn register_user(
         &self,
@@ -445,6 +1623,35 @@ pub trait ProvideUserRegistry: Send + Sync {
         let ctx = self.context();
         self.user_registry().register_user(ctx, params)
     }
+    fn update_user(
+        &self,
+        params: UpdateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().update_user(ctx, params)
+    }
+    fn delete_user(
+        &self,
+        params: DeleteUserParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().delete_user(ctx, params)
+    }
+    fn set_user_status(
+        &self,
+        params: SetUserStatusParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().set_user_status(ctx, params)
+    }
+    fn purge_deleted_users(
+        &self,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> impl Future<Output = Result<u64, Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry()
+            .purge_deleted_users(ctx, deleted_before)
+    }
     fn verify_user_password(
         &self,
         params: VerifyUserPasswordParams,
@@ -452,6 +1659,13 @@ pub trait ProvideUserRegistry: Send + Sync {
         let ctx = self.context();
         self.user_registry().verify_user_password(ctx, params)
     }
+    fn authenticate_user(
+        &self,
+        params: AuthenticateUserParams,
+    ) -> impl Future<Output = Result<User, Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().authenticate_user(ctx, params)
+    }
     fn update_user_password(
         &self,
         params: UpdateUserPasswordParams,
@@ -459,6 +1673,27 @@ pub trait ProvideUserRegistry: Send + Sync {
         let ctx = self.context();
         self.user_registry().update_user_password(ctx, params)
     }
+    fn get_user_roles(
+        &self,
+        user_id: UserId,
+    ) -> impl Future<Output = Result<Vec<Role>, Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().get_user_roles(ctx, user_id)
+    }
+    fn grant_role(
+        &self,
+        params: GrantRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().grant_role(ctx, params)
+    }
+    fn revoke_role(
+        &self,
+        params: RevokeRoleParams,
+    ) -> impl Future<Output = Result<(), Failure>> + Send {
+        let ctx = self.context();
+        self.user_registry().revoke_role(ctx, params)
+    }
 }
 
 impl<T> ProvideUserRegistry for &T
@@ -481,3 +1716,113 @@ where
         T::user_registry(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_grants() {
+        let cases = [
+            ("users:read", "users:read", true),
+            ("users:read", "users:write", false),
+            ("users:read", "users", false),
+            ("users:read", "users:read:all", false),
+            ("users:*", "users:read", true),
+            ("users:*", "users:", true),
+            ("users:*", "users", false),
+            ("users:*", "usersx:read", false),
+            ("users*", "users_admin:read", true),
+            ("*", "users:read", true),
+            ("*", "", true),
+            ("", "users:read", false),
+            // only a trailing `*` is a wildcard
+            ("*:read", "users:read", false),
+            ("*:read", "*:read", true),
+        ];
+        for (permission, required, expected) in cases {
+            let permission = Permission(permission.to_string());
+            assert_eq!(
+                permission.grants(required),
+                expected,
+                "{} grants {required}",
+                permission.0
+            );
+        }
+    }
+
+    #[test]
+    fn principal_has_any_granting_permission() {
+        let principal = Principal {
+            user_id: UserId(uuid::Uuid::nil()),
+            session_id: SessionId(uuid::Uuid::nil()),
+            roles: vec!["auditor".to_string()],
+            permissions: vec![
+                Permission("users:read".to_string()),
+                Permission("audit:*".to_string()),
+            ],
+        };
+        assert!(principal.has_permission("users:read"));
+        assert!(principal.has_permission("audit:read"));
+        assert!(!principal.has_permission("users:write"));
+        let nobody = Principal {
+            permissions: Vec::new(),
+            ..principal
+        };
+        assert!(!nobody.has_permission("users:read"));
+    }
+
+    fn cursor(json: &str) -> UserCursor {
+        use base64::Engine as _;
+
+        UserCursor(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json))
+    }
+
+    #[test]
+    fn user_cursor_round_trips_for_its_order() {
+        let cursor = UserCursor::new(UserOrderBy::Name, "Ada \"L\" ☃", "ada").unwrap();
+        assert!(!cursor.0.contains(['+', '/', '=']), "{}", cursor.0);
+        assert_eq!(
+            cursor.decode(UserOrderBy::Name).ok(),
+            Some(("Ada \"L\" ☃".to_string(), "ada".to_string()))
+        );
+    }
+
+    #[test]
+    fn user_cursor_rejects_another_order() {
+        let cursor = UserCursor::new(UserOrderBy::Name, "Ada", "ada").unwrap();
+        assert!(cursor.decode(UserOrderBy::DisplayId).is_err());
+        let cursor = UserCursor::new(UserOrderBy::DisplayId, "ada", "ada").unwrap();
+        assert!(cursor.decode(UserOrderBy::Name).is_err());
+    }
+
+    #[test]
+    fn user_cursor_rejects_tampering() {
+        let valid = UserCursor::new(UserOrderBy::DisplayId, "ada", "ada").unwrap();
+        let truncated = UserCursor(valid.0[..valid.0.len() - 3].to_string());
+        let padded = UserCursor(format!("{}=", valid.0));
+        let cases = [
+            ("empty", UserCursor(String::new())),
+            ("not base64", UserCursor("not a cursor!".to_string())),
+            ("hex of the old format", UserCursor("5b5d".to_string())),
+            ("truncated", truncated),
+            ("padded", padded),
+            ("not JSON", cursor("ada")),
+            (
+                "missing field",
+                cursor(r#"{"order_by":"display_id","key":"ada"}"#),
+            ),
+            (
+                "unknown order",
+                cursor(r#"{"order_by":"id","key":"ada","display_id":"ada"}"#),
+            ),
+            (
+                "wrong type",
+                cursor(r#"{"order_by":"display_id","key":1,"display_id":"ada"}"#),
+            ),
+        ];
+        for (name, cursor) in cases {
+            assert!(cursor.decode(UserOrderBy::DisplayId).is_err(), "{name}");
+        }
+    }
+}
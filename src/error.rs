@@ -7,18 +7,37 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "snake_case")]
 pub enum RejectKind {
     Unauthorized,
+    Forbidden,
     BadRequest,
     NotFound,
     Conflict,
+    TooManyRequests,
+}
+
+impl RejectKind {
+    /// The snake case name, as serialized.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::BadRequest => "bad_request",
+            Self::NotFound => "not_found",
+            Self::Conflict => "conflict",
+            Self::TooManyRequests => "too_many_requests",
+        }
+    }
 }
 
 impl fmt::Display for RejectKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
             Self::Unauthorized => "Unauthorized",
+            Self::Forbidden => "Forbidden",
             Self::BadRequest => "Bad request",
             Self::NotFound => "Not found",
             Self::Conflict => "Conflict",
+            Self::TooManyRequests => "Too many requests",
         };
         f.write_str(s)
     }
@@ -28,6 +47,9 @@ impl fmt::Display for RejectKind {
 pub struct Reject {
     kind: RejectKind,
     message: String,
+    /// Machine-readable code telling clients why the request was rejected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
 }
 
 impl fmt::Display for Reject {
@@ -37,13 +59,20 @@ impl fmt::Display for Reject {
 }
 
 impl Reject {
+    #[must_use]
     pub fn kind(&self) -> RejectKind {
         self.kind
     }
 
+    #[must_use]
     pub fn message(&self) -> &str {
         &self.message
     }
+
+    #[must_use]
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -71,6 +100,16 @@ impl Failure {
         Reject {
             kind: RejectKind::Unauthorized,
             message: message.into(),
+            reason: None,
+        }
+        .into()
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Reject {
+            kind: RejectKind::Forbidden,
+            message: message.into(),
+            reason: None,
         }
         .into()
     }
@@ -79,6 +118,16 @@ impl Failure {
         Reject {
             kind: RejectKind::BadRequest,
             message: message.into(),
+            reason: None,
+        }
+        .into()
+    }
+
+    pub fn bad_request_with_reason(reason: impl Into<String>, message: impl Into<String>) -> Self {
+        Reject {
+            kind: RejectKind::BadRequest,
+            message: message.into(),
+            reason: Some(reason.into()),
         }
         .into()
     }
@@ -87,6 +136,7 @@ impl Failure {
         Reject {
             kind: RejectKind::NotFound,
             message: message.into(),
+            reason: None,
         }
         .into()
     }
@@ -95,6 +145,16 @@ impl Failure {
         Reject {
             kind: RejectKind::Conflict,
             message: message.into(),
+            reason: None,
+        }
+        .into()
+    }
+
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        Reject {
+            kind: RejectKind::TooManyRequests,
+            message: message.into(),
+            reason: None,
         }
         .into()
     }
@@ -0,0 +1,112 @@
+//! Liveness and readiness reporting for load balancers and orchestrators.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// How long a single dependency check may take before it counts as failed.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether the server still accepts new traffic, shared between the router and the signal handler.
+#[must_use]
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    draining: Arc<AtomicBool>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails readiness from now on, so that load balancers stop routing here.
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct CheckReport {
+    pub name: &'static str,
+    pub ok: bool,
+    pub latency_ms: f64,
+    /// A generic message; the cause is only logged, as probes may be reachable by anyone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[must_use]
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub draining: bool,
+    pub checks: Vec<CheckReport>,
+}
+
+impl ReadinessReport {
+    pub fn new(draining: bool, checks: Vec<CheckReport>) -> Self {
+        let ready = !draining && checks.iter().all(|c| c.ok);
+        Self {
+            ready,
+            draining,
+            checks,
+        }
+    }
+}
+
+/// Runs one dependency check under [`CHECK_TIMEOUT`], timing it.
+pub async fn check<F>(name: &'static str, check: F) -> CheckReport
+where
+    F: Future<Output = anyhow::Result<()>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!("Timed out after {CHECK_TIMEOUT:?}")),
+    };
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    if let Err(e) = &result {
+        tracing::warn!(
+            check = name,
+            error = format!("{e:#}"),
+            "Readiness check failed"
+        );
+    }
+    CheckReport {
+        name,
+        ok: result.is_ok(),
+        latency_ms,
+        error: result.err().map(|_| "Check failed".to_string()),
+    }
+}
+
+pub trait HealthCheck: Send + Sync {
+    fn readiness(&self) -> &Readiness;
+
+    /// Checks every dependency needed to serve requests.
+    fn check_readiness(&self) -> impl Future<Output = ReadinessReport> + Send;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_report_hides_the_cause() {
+        let report = check("database", async {
+            Err(anyhow::anyhow!("Access denied for user 'app'@'10.0.0.7'"))
+        })
+        .await;
+        assert!(!report.ok);
+        assert_eq!(report.error.as_deref(), Some("Check failed"));
+        let ok = check("database", async { Ok(()) }).await;
+        assert!(ok.ok && ok.error.is_none());
+    }
+}
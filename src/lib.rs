@@ -1,20 +1,68 @@
+pub mod audit;
+pub mod config;
 pub mod entity;
-mod error;
+pub mod error;
+pub mod health;
+pub mod listener;
+mod metrics;
+pub mod password;
 pub mod provide;
+pub mod proxy;
 mod registry;
 mod repository;
 mod router;
+pub mod secret;
+pub mod telemetry;
+#[cfg(feature = "tls")]
+pub mod tls;
 pub mod token;
 
+pub use config::Config;
 use error::Failure;
-pub use provide::{State, StateInit};
-pub use registry::Registry;
-pub use repository::Repository;
-pub use router::make as make_router;
+pub use provide::{BootstrapAdmin, State, StateInit};
+pub use registry::{Registry, purge_deleted_users_periodically};
+pub use repository::{MigrationStatus, Repository};
+pub use router::{
+    Assets, ClientInfo, CookiePolicy, CorsPolicy, Limits, OriginPattern, RouteConfig, RouteHeaders,
+    SecurityHeaders, SecurityPolicy, StateRequirements, TlsConnection, make as make_router,
+};
+pub use secret::Secret;
 
-#[tracing::instrument]
-pub async fn signal_handler() {
-    if let Err(e) = tokio::signal::ctrl_c().await {
-        tracing::error!("{e}");
+/// Waits for Ctrl-C or SIGTERM, then fails readiness and keeps serving for `drain_delay` so that
+/// load balancers stop routing here before the listener closes. A second signal skips the delay.
+#[tracing::instrument(skip(readiness))]
+pub async fn signal_handler(readiness: health::Readiness, drain_delay: std::time::Duration) {
+    shutdown_signal().await;
+    readiness.drain();
+    tracing::info!("Draining before shutdown");
+    tokio::select! {
+        () = tokio::time::sleep(drain_delay) => {}
+        () = shutdown_signal() => tracing::info!("Skipping the drain delay"),
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("{e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            if let Err(e) = result {
+                tracing::error!("{e}");
+            }
+        }
+        () = terminate => {}
     }
 }
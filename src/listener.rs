@@ -0,0 +1,90 @@
+//! TCP listening with per-connection preparation, such as a TLS handshake or a PROXY header.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// How long a connection may take to be prepared, e.g. to complete its TLS handshake.
+pub const PREPARE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts TCP connections and yields them once `prepare` succeeds, along with the client
+/// address it returns.
+///
+/// Connections are prepared on their own tasks, so that a slow client does not hold up the others.
+#[must_use]
+pub struct PreparedListener<Io> {
+    local_addr: SocketAddr,
+    incoming: mpsc::Receiver<(Io, SocketAddr)>,
+}
+
+impl<Io> PreparedListener<Io>
+where
+    Io: Send + 'static,
+{
+    pub fn new<F, Fut>(listener: TcpListener, prepare: F) -> io::Result<Self>
+    where
+        F: Fn(TcpStream, SocketAddr) -> Fut + Send + 'static,
+        Fut: Future<Output = io::Result<(Io, SocketAddr)>> + Send + 'static,
+    {
+        let local_addr = listener.local_addr()?;
+        let (tx, incoming) = mpsc::channel(64);
+        tokio::spawn(accept_loop(listener, prepare, tx));
+        Ok(Self {
+            local_addr,
+            incoming,
+        })
+    }
+}
+
+async fn accept_loop<Io, F, Fut>(
+    mut listener: TcpListener,
+    prepare: F,
+    tx: mpsc::Sender<(Io, SocketAddr)>,
+) where
+    Io: Send + 'static,
+    F: Fn(TcpStream, SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<(Io, SocketAddr)>> + Send + 'static,
+{
+    loop {
+        // stop listening once the server drops the receiving end
+        let (stream, addr) = tokio::select! {
+            accepted = axum::serve::Listener::accept(&mut listener) => accepted,
+            () = tx.closed() => return,
+        };
+        let prepared = prepare(stream, addr);
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(PREPARE_TIMEOUT, prepared).await {
+                Ok(Ok(prepared)) => {
+                    let _ = tx.send(prepared).await;
+                }
+                Ok(Err(e)) => tracing::debug!(%addr, error = %e, "Dropping connection"),
+                Err(_) => tracing::debug!(%addr, "Dropping connection that timed out"),
+            }
+        });
+    }
+}
+
+impl<Io> axum::serve::Listener for PreparedListener<Io>
+where
+    Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = Io;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(accepted) => accepted,
+            // the accept loop only ends after this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
@@ -0,0 +1,13 @@
+//! Prometheus metrics for auth flows and dependencies, served on `/metrics`.
+//!
+//! Recorded only with the `metrics` feature; without it every function here is a no-op.
+
+#[cfg(not(feature = "metrics"))]
+mod disabled;
+#[cfg(feature = "metrics")]
+mod enabled;
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) use disabled::*;
+#[cfg(feature = "metrics")]
+pub(crate) use enabled::*;
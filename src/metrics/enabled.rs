@@ -0,0 +1,265 @@
+use std::sync::{LazyLock, OnceLock};
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+
+use crate::Failure;
+
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    logins: IntCounterVec,
+    registrations: IntCounterVec,
+    credential_checks: IntCounterVec,
+    hashing_duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The pool sampled on every scrape.
+static POOL: OnceLock<sqlx::MySqlPool> = OnceLock::new();
+
+fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter");
+    registry
+        .register(Box::new(counter.clone()))
+        .expect("unique counter");
+    counter
+}
+
+fn histogram(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    let histogram =
+        HistogramVec::new(HistogramOpts::new(name, help), labels).expect("valid histogram");
+    registry
+        .register(Box::new(histogram.clone()))
+        .expect("unique histogram");
+    histogram
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let r = &registry;
+        let requests = counter(
+            r,
+            "http_requests_total",
+            "HTTP requests by route, method and status",
+            &["route", "method", "status"],
+        );
+        let request_duration = histogram(
+            r,
+            "http_request_duration_seconds",
+            "HTTP request latency by route and method",
+            &["route", "method"],
+        );
+        let logins = counter(
+            r,
+            "auth_logins_total",
+            "Login attempts by outcome",
+            &["outcome"],
+        );
+        let registrations = counter(
+            r,
+            "auth_registrations_total",
+            "Registration attempts by outcome",
+            &["outcome"],
+        );
+        let credential_checks = counter(
+            r,
+            "auth_credential_checks_total",
+            "Session credential checks by outcome",
+            &["outcome"],
+        );
+        let hashing_duration = histogram(
+            r,
+            "password_hashing_duration_seconds",
+            "Time spent hashing or verifying a password, excluding queueing",
+            &["operation"],
+        );
+        let pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .expect("valid gauge");
+        registry
+            .register(Box::new(pool_connections.clone()))
+            .expect("unique gauge");
+        Self {
+            registry,
+            requests,
+            request_duration,
+            logins,
+            registrations,
+            credential_checks,
+            hashing_duration,
+            pool_connections,
+        }
+    }
+}
+
+/// Labels a result by its rejection reason or kind, which come from a small fixed set.
+fn outcome<'a, T>(result: &'a Result<T, Failure>, success: &'a str) -> &'a str {
+    match result {
+        Ok(_) => success,
+        Err(Failure::Reject(r)) => r.reason().unwrap_or(r.kind().as_str()),
+        Err(Failure::Error(_)) => "error",
+    }
+}
+
+pub(crate) fn record_login<T>(result: &Result<T, Failure>) {
+    let outcome = outcome(result, "success");
+    METRICS.logins.with_label_values(&[outcome]).inc();
+}
+
+pub(crate) fn record_registration<T>(result: &Result<T, Failure>) {
+    let outcome = outcome(result, "success");
+    METRICS.registrations.with_label_values(&[outcome]).inc();
+}
+
+pub(crate) fn record_credential_check<T>(result: &Result<T, Failure>) {
+    let outcome = outcome(result, "valid");
+    METRICS
+        .credential_checks
+        .with_label_values(&[outcome])
+        .inc();
+}
+
+pub(crate) fn observe_hashing(operation: &'static str, elapsed: Duration) {
+    METRICS
+        .hashing_duration
+        .with_label_values(&[operation])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Samples `pool` on every scrape; only the first pool is kept.
+pub(crate) fn watch_pool(pool: &sqlx::MySqlPool) {
+    let _ = POOL.set(pool.clone());
+}
+
+fn sample_pool(pool: &sqlx::MySqlPool) {
+    let size = i64::from(pool.size());
+    let idle = i64::try_from(pool.num_idle()).unwrap_or(i64::MAX);
+    let max = i64::from(pool.options().get_max_connections());
+    let gauges = &METRICS.pool_connections;
+    gauges.with_label_values(&["idle"]).set(idle);
+    gauges.with_label_values(&["active"]).set(size - idle);
+    gauges.with_label_values(&["max"]).set(max);
+}
+
+async fn render() -> Response {
+    use prometheus::Encoder;
+
+    if let Some(pool) = POOL.get() {
+        sample_pool(pool);
+    }
+    let encoder = prometheus::TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&METRICS.registry.gather(), &mut body) {
+        tracing::error!(error = %e, "Failed to encode metrics");
+        return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let content_type = encoder.format_type().to_string();
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+/// Adds the `/metrics` route.
+pub(crate) fn route<S>(router: axum::Router<S>) -> axum::Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.route("/metrics", axum::routing::get(render))
+}
+
+/// Counts and times every route added to `router` so far, labelled by its path template.
+pub(crate) fn track<S>(router: axum::Router<S>) -> axum::Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.route_layer(axum::middleware::from_fn(track_request))
+}
+
+async fn track_request(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let method = req.method().clone();
+    let start = Instant::now();
+    let res = next.run(req).await;
+    let elapsed = start.elapsed().as_secs_f64();
+    let labels = [route.as_str(), method.as_str()];
+    METRICS
+        .request_duration
+        .with_label_values(&labels)
+        .observe(elapsed);
+    let status = res.status();
+    METRICS
+        .requests
+        .with_label_values(&[route.as_str(), method.as_str(), status.as_str()])
+        .inc();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    #[test]
+    fn outcomes_are_labelled_by_reason_or_kind() {
+        let cases: [(Result<(), Failure>, &str); 4] = [
+            (Ok(()), "success"),
+            (
+                Err(Failure::unauthorized("Password mismatch")),
+                "unauthorized",
+            ),
+            (
+                Err(Failure::bad_request_with_reason(
+                    "common_password",
+                    "Password is too common",
+                )),
+                "common_password",
+            ),
+            (Err(anyhow::anyhow!("database is down").into()), "error"),
+        ];
+        for (result, expected) in &cases {
+            assert_eq!(outcome(result, "success"), *expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_counted_by_route_template() {
+        let router = track(
+            axum::Router::new().route("/metrics-test/{id}", axum::routing::get(|| async { "ok" })),
+        );
+        let router = route(router);
+        for id in ["1", "2"] {
+            let req = Request::get(format!("/metrics-test/{id}"))
+                .body(Body::empty())
+                .unwrap();
+            router.clone().oneshot(req).await.unwrap();
+        }
+        let req = Request::get("/metrics").body(Body::empty()).unwrap();
+        let res = router.oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let line = body
+            .lines()
+            .find(|l| l.starts_with("http_requests_total") && l.contains("/metrics-test/{id}"))
+            .expect("route counted");
+        assert!(line.contains(r#"method="GET""#), "{line}");
+        assert!(line.contains(r#"status="200""#), "{line}");
+        assert!(line.ends_with(" 2"), "{line}");
+    }
+}
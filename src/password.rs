@@ -0,0 +1,335 @@
+mod policy;
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::{Failure, Secret};
+
+pub use policy::Policy;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    Bcrypt,
+    #[default]
+    Argon2id,
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bcrypt" => Ok(Self::Bcrypt),
+            "argon2id" => Ok(Self::Argon2id),
+            _ => Err(anyhow::anyhow!("Unknown password hash algorithm {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Verification {
+    pub matches: bool,
+    /// Whether the stored hash is weaker than what [`PasswordHasher`] would produce now.
+    pub needs_rehash: bool,
+}
+
+/// Hashes passwords with the preferred algorithm, and verifies hashes of any supported one.
+///
+/// Hashes are stored in PHC string format (`$argon2id$v=19$m=...`) or, for bcrypt, the
+/// modular crypt format (`$2b$12$...`).
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct PasswordHasher {
+    preferred: Algorithm,
+    bcrypt_cost: u32,
+    argon2_params: argon2::Params,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self::new(Algorithm::default())
+    }
+}
+
+impl PasswordHasher {
+    pub fn new(preferred: Algorithm) -> Self {
+        Self {
+            preferred,
+            bcrypt_cost: bcrypt::DEFAULT_COST,
+            argon2_params: argon2::Params::DEFAULT,
+        }
+    }
+
+    pub fn bcrypt_cost(self, bcrypt_cost: u32) -> Self {
+        Self {
+            bcrypt_cost,
+            ..self
+        }
+    }
+
+    pub fn argon2_params(self, argon2_params: argon2::Params) -> Self {
+        Self {
+            argon2_params,
+            ..self
+        }
+    }
+
+    fn argon2(&self) -> argon2::Argon2<'static> {
+        argon2::Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::default(),
+            self.argon2_params.clone(),
+        )
+    }
+
+    pub fn hash(&self, raw: &str) -> anyhow::Result<String> {
+        use argon2::PasswordHasher as _;
+
+        match self.preferred {
+            Algorithm::Bcrypt => {
+                bcrypt::hash(raw, self.bcrypt_cost).context("Failed to hash password with bcrypt")
+            }
+            Algorithm::Argon2id => {
+                let hash = self
+                    .argon2()
+                    .hash_password(raw.as_bytes())
+                    .map_err(|e| anyhow::anyhow!("Failed to hash password with argon2: {e}"))?;
+                Ok(hash.to_string())
+            }
+        }
+    }
+
+    pub fn verify(&self, raw: &str, hash: &str) -> anyhow::Result<Verification> {
+        if hash.starts_with("$2") {
+            self.verify_bcrypt(raw, hash)
+        } else if hash.starts_with("$argon2") {
+            self.verify_argon2(raw, hash)
+        } else {
+            anyhow::bail!("Unknown password hash format")
+        }
+    }
+
+    fn verify_bcrypt(&self, raw: &str, hash: &str) -> anyhow::Result<Verification> {
+        let parts: bcrypt::HashParts = hash.parse().context("Failed to parse bcrypt hash")?;
+        let matches = bcrypt::verify(raw, hash).context("Failed to challenge bcrypt hash")?;
+        let needs_rehash =
+            self.preferred != Algorithm::Bcrypt || parts.get_cost() < self.bcrypt_cost;
+        Ok(Verification {
+            matches,
+            needs_rehash,
+        })
+    }
+
+    fn verify_argon2(&self, raw: &str, hash: &str) -> anyhow::Result<Verification> {
+        use argon2::PasswordVerifier as _;
+
+        let parsed = argon2::PasswordHash::new(hash)
+            .map_err(|e| anyhow::anyhow!("Failed to parse argon2 hash: {e}"))?;
+        let params = argon2::Params::try_from(&parsed)
+            .map_err(|e| anyhow::anyhow!("Failed to parse argon2 params: {e}"))?;
+        // the parameters embedded in the hash take precedence over the configured ones
+        let matches = match self.argon2().verify_password(raw.as_bytes(), &parsed) {
+            Ok(()) => true,
+            Err(argon2::password_hash::Error::PasswordInvalid) => false,
+            Err(e) => anyhow::bail!("Failed to challenge argon2 hash: {e}"),
+        };
+        let configured = &self.argon2_params;
+        let needs_rehash = self.preferred != Algorithm::Argon2id
+            || parsed.algorithm.as_str() != argon2::ARGON2ID_IDENT.as_str()
+            || params.m_cost() < configured.m_cost()
+            || params.t_cost() < configured.t_cost()
+            || params.p_cost() < configured.p_cost();
+        Ok(Verification {
+            matches,
+            needs_rehash,
+        })
+    }
+}
+
+/// Runs a [`PasswordHasher`] on the blocking thread pool, so hashing never stalls the runtime.
+///
+/// At most `workers` hashes run at once. Once `queue_depth` hashes are running or waiting,
+/// further requests are rejected with [`Failure::too_many_requests`] instead of piling up.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct HashingPool {
+    hasher: Arc<PasswordHasher>,
+    workers: Arc<Semaphore>,
+    queue: Arc<Semaphore>,
+    /// A hash matching no password, verified for accounts without one.
+    dummy: Arc<str>,
+}
+
+impl HashingPool {
+    /// Hashes the dummy up front, so that the first unknown account is not slower.
+    pub fn new(hasher: PasswordHasher, workers: usize, queue_depth: usize) -> anyhow::Result<Self> {
+        let dummy = hasher
+            .hash(&uuid::Uuid::new_v4().to_string())
+            .context("Failed to hash dummy password")?;
+        Ok(Self {
+            hasher: Arc::new(hasher),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            queue: Arc::new(Semaphore::new(queue_depth.max(workers).max(1))),
+            dummy: dummy.into(),
+        })
+    }
+
+    async fn run<T, F>(&self, operation: &'static str, f: F) -> Result<T, Failure>
+    where
+        T: Send + 'static,
+        F: FnOnce(&PasswordHasher) -> anyhow::Result<T> + Send + 'static,
+    {
+        let Ok(queued) = Arc::clone(&self.queue).try_acquire_owned() else {
+            tracing::warn!("Password hashing queue is full");
+            return Err(Failure::too_many_requests(
+                "Server is busy, try again later",
+            ));
+        };
+        let worker = Arc::clone(&self.workers)
+            .acquire_owned()
+            .await
+            .context("Password hashing pool is closed")?;
+        let hasher = Arc::clone(&self.hasher);
+        // the permits go with the hash, which runs on even if the caller gives up waiting for it
+        let res = tokio::task::spawn_blocking(move || {
+            let _permits = (queued, worker);
+            let start = std::time::Instant::now();
+            let res = f(&hasher);
+            crate::metrics::observe_hashing(operation, start.elapsed());
+            res
+        })
+        .await
+        .context("Password hashing task panicked")??;
+        Ok(res)
+    }
+
+    pub async fn hash(&self, raw: Secret) -> Result<String, Failure> {
+        self.run("hash", move |hasher| hasher.hash(raw.as_str()))
+            .await
+    }
+
+    pub async fn verify(&self, raw: Secret, hash: String) -> Result<Verification, Failure> {
+        self.run("verify", move |hasher| hasher.verify(raw.as_str(), &hash))
+            .await
+    }
+
+    /// Verifies `raw` against a dummy hash, for an account without a password.
+    ///
+    /// Spends the same work as [`HashingPool::verify`] on an account hashed with the preferred
+    /// algorithm, so that missing accounts cannot be told apart by response time.
+    pub async fn verify_dummy(&self, raw: Secret) -> Result<(), Failure> {
+        let dummy = Arc::clone(&self.dummy);
+        self.run("verify", move |hasher| {
+            hasher.verify(raw.as_str(), &dummy)?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(preferred: Algorithm) -> HashingPool {
+        let params = argon2::Params::new(8, 1, 1, None).unwrap();
+        let hasher = PasswordHasher::new(preferred)
+            .bcrypt_cost(4)
+            .argon2_params(params);
+        HashingPool::new(hasher, 1, 1).unwrap()
+    }
+
+    #[test]
+    fn dummy_uses_the_preferred_algorithm() {
+        assert!(pool(Algorithm::Argon2id).dummy.starts_with("$argon2id$"));
+        assert!(pool(Algorithm::Bcrypt).dummy.starts_with("$2"));
+    }
+
+    #[test]
+    fn outdated_hashes_need_rehash() {
+        let weak = argon2::Params::new(8, 1, 1, None).unwrap();
+        let strong = argon2::Params::new(16, 2, 1, None).unwrap();
+        let bcrypt = |cost| PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(cost);
+        let argon2 = |params| PasswordHasher::new(Algorithm::Argon2id).argon2_params(params);
+        let cases = [
+            ("same bcrypt cost", bcrypt(4), bcrypt(4), false),
+            ("raised bcrypt cost", bcrypt(4), bcrypt(5), true),
+            ("lowered bcrypt cost", bcrypt(5), bcrypt(4), false),
+            ("bcrypt to argon2id", bcrypt(4), argon2(weak.clone()), true),
+            ("argon2id to bcrypt", argon2(weak.clone()), bcrypt(4), true),
+            (
+                "same argon2 params",
+                argon2(weak.clone()),
+                argon2(weak.clone()),
+                false,
+            ),
+            (
+                "raised argon2 params",
+                argon2(weak.clone()),
+                argon2(strong.clone()),
+                true,
+            ),
+            ("lowered argon2 params", argon2(strong), argon2(weak), false),
+        ];
+        for (name, old, new, needs_rehash) in cases {
+            let hash = old.hash("correct horse").unwrap();
+            let verification = new.verify("correct horse", &hash).unwrap();
+            assert!(verification.matches, "{name}");
+            assert_eq!(verification.needs_rehash, needs_rehash, "{name}");
+            assert!(!new.verify("wrong horse", &hash).unwrap().matches, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn abandoned_hashes_keep_their_worker() {
+        let pool = HashingPool {
+            queue: Arc::new(Semaphore::new(2)),
+            ..pool(Algorithm::Argon2id)
+        };
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let first = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run("test", move |_| {
+                    let _ = started_tx.send(());
+                    let _ = release_rx.recv();
+                    Ok(())
+                })
+                .await
+            }
+        });
+        started_rx.await.unwrap();
+        // the caller goes away, as on a client disconnect or timeout
+        first.abort();
+        assert!(first.await.unwrap_err().is_cancelled());
+        assert_eq!(pool.workers.available_permits(), 0);
+        assert_eq!(pool.queue.available_permits(), 1);
+
+        let (second_tx, mut second_rx) = tokio::sync::oneshot::channel();
+        let second = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run("test", move |_| {
+                    let _ = second_tx.send(());
+                    Ok(())
+                })
+                .await
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(
+            second_rx.try_recv().is_err(),
+            "started while a hash was running"
+        );
+
+        release_tx.send(()).unwrap();
+        second.await.unwrap().unwrap();
+        assert_eq!(pool.workers.available_permits(), 1);
+        assert_eq!(pool.queue.available_permits(), 2);
+    }
+}
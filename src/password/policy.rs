@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use sha1::{Digest, Sha1};
+
+use crate::Failure;
+
+const COMMON_PASSWORDS: &str = include_str!("common-passwords.txt");
+
+/// Rejects passwords found in a list of common passwords or, when configured, in an offline
+/// copy of the Have I Been Pwned range files.
+///
+/// The range directory holds one file per 5-hex-digit SHA-1 prefix, named `<PREFIX>` or
+/// `<PREFIX>.txt`, with `SUFFIX:COUNT` lines as served by the k-anonymity range API.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct Policy {
+    common: Arc<HashSet<String>>,
+    range_dir: Option<PathBuf>,
+    min_breach_count: u64,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy {
+    /// Creates a policy checking against the bundled common-password list only.
+    pub fn new() -> Self {
+        Self {
+            common: Arc::new(COMMON_PASSWORDS.lines().map(str::to_lowercase).collect()),
+            range_dir: None,
+            min_breach_count: 1,
+        }
+    }
+
+    /// Replaces the bundled common-password list.
+    pub fn common_passwords<I>(self, passwords: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let common = passwords
+            .into_iter()
+            .map(|p| p.as_ref().to_lowercase())
+            .collect();
+        Self {
+            common: Arc::new(common),
+            ..self
+        }
+    }
+
+    /// Adds the passwords listed one per line in `path` to the common-password list.
+    pub fn common_passwords_file(self, path: &Path) -> anyhow::Result<Self> {
+        let list = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut common = HashSet::clone(&self.common);
+        common.extend(
+            list.lines()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_lowercase),
+        );
+        Ok(Self {
+            common: Arc::new(common),
+            ..self
+        })
+    }
+
+    pub fn range_dir(self, range_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            range_dir: Some(range_dir.into()),
+            ..self
+        }
+    }
+
+    /// Sets how many times a password must have been seen in breaches to be rejected.
+    pub fn min_breach_count(self, min_breach_count: u64) -> Self {
+        Self {
+            min_breach_count,
+            ..self
+        }
+    }
+
+    fn is_common(&self, raw: &str) -> bool {
+        self.common.contains(&raw.to_lowercase())
+    }
+
+    /// Returns how often `raw` appears in the range files, or `None` without a range directory.
+    async fn breach_count(&self, raw: &str) -> anyhow::Result<Option<u64>> {
+        let Some(range_dir) = self.range_dir.clone() else {
+            return Ok(None);
+        };
+        let digest = format!("{:X}", Sha1::digest(raw.as_bytes()));
+        let (prefix, suffix) = digest.split_at(5);
+        let (prefix, suffix) = (prefix.to_string(), suffix.to_string());
+        let count = tokio::task::spawn_blocking(move || {
+            let Some(path) = [format!("{prefix}.txt"), prefix]
+                .into_iter()
+                .map(|name| range_dir.join(name))
+                .find(|path| path.is_file())
+            else {
+                tracing::warn!(dir = %range_dir.display(), "Missing password range file");
+                return Ok(0);
+            };
+            let ranges = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let count = ranges
+                .lines()
+                .filter_map(|line| line.trim_end().split_once(':'))
+                .find(|(s, _)| s.eq_ignore_ascii_case(&suffix))
+                .map_or(Ok(0), |(_, count)| count.parse())
+                .with_context(|| format!("Malformed range file {}", path.display()))?;
+            anyhow::Ok(count)
+        })
+        .await
+        .context("Password range lookup panicked")??;
+        Ok(Some(count))
+    }
+}
+
+impl<Context> crate::entity::PasswordPolicy<Context> for Policy
+where
+    Context: Send,
+{
+    async fn check_password(
+        &self,
+        _ctx: Context,
+        params: crate::entity::CheckPasswordParams,
+    ) -> Result<(), Failure> {
+        let crate::entity::CheckPasswordParams { raw } = params;
+        if self.is_common(raw.as_str()) {
+            return Err(Failure::bad_request_with_reason(
+                "common_password",
+                "Password is too common",
+            ));
+        }
+        if let Some(count) = self.breach_count(raw.as_str()).await?
+            && count >= self.min_breach_count
+        {
+            return Err(Failure::bad_request_with_reason(
+                "breached_password",
+                format!("Password has appeared in {count} data breaches"),
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,32 @@
+use crate::Failure;
+
+/// The user granted the admin role on [`State::setup`], registered first if missing.
+#[derive(Clone)]
+pub struct BootstrapAdmin {
+    pub display_id: String,
+    pub name: String,
+    pub raw_password: crate::Secret,
+}
+
 #[derive(Clone)]
 pub struct StateInit {
     pub cookie_name: String,
     pub path_prefix: String,
+    pub cookie_policy: crate::CookiePolicy,
+    pub trusted_origins: Vec<String>,
+    pub cors: crate::CorsPolicy,
+    pub limits: crate::Limits,
+    pub trusted_proxies: crate::proxy::TrustedProxies,
+    pub assets: crate::Assets,
+    pub security: crate::SecurityPolicy,
     pub pool: sqlx::MySqlPool,
     pub repo: crate::repository::Repository,
+    /// Apply pending migrations on [`State::setup`] instead of refusing to start.
+    pub auto_migrate: bool,
     pub jwt: crate::token::Jwt,
+    pub audit_log: crate::audit::AuditLogger,
+    pub password_policy: crate::password::Policy,
+    pub bootstrap_admin: Option<BootstrapAdmin>,
 }
 
 #[must_use]
@@ -12,10 +34,22 @@ pub struct StateInit {
 pub struct State {
     cookie_name: String,
     path_prefix: String,
+    cookie_policy: crate::CookiePolicy,
+    trusted_origins: Vec<String>,
+    cors: crate::CorsPolicy,
+    limits: crate::Limits,
+    trusted_proxies: crate::proxy::TrustedProxies,
+    assets: crate::Assets,
+    security: crate::SecurityPolicy,
     pool: sqlx::MySqlPool,
     repo: crate::repository::Repository,
+    auto_migrate: bool,
     jwt: crate::token::Jwt,
+    audit_log: crate::audit::AuditLogger,
+    password_policy: crate::password::Policy,
     registry: crate::registry::Registry,
+    bootstrap_admin: Option<BootstrapAdmin>,
+    readiness: crate::health::Readiness,
 }
 
 impl crate::router::RouteConfig for State {
@@ -26,18 +60,64 @@ impl crate::router::RouteConfig for State {
     fn path_prefix(&self) -> &str {
         &self.path_prefix
     }
+
+    fn cookie_policy(&self) -> &crate::CookiePolicy {
+        &self.cookie_policy
+    }
+
+    fn trusted_origins(&self) -> &[String] {
+        &self.trusted_origins
+    }
+
+    fn cors(&self) -> &crate::CorsPolicy {
+        &self.cors
+    }
+
+    fn limits(&self) -> &crate::Limits {
+        &self.limits
+    }
+
+    fn trusted_proxies(&self) -> &crate::proxy::TrustedProxies {
+        &self.trusted_proxies
+    }
+
+    fn assets(&self) -> &crate::Assets {
+        &self.assets
+    }
+
+    fn security(&self) -> &crate::SecurityPolicy {
+        &self.security
+    }
 }
 
 impl crate::entity::ProvideCredentialManager for State {
-    type Context<'a> = ();
+    type Context<'a> = RepoCtx<'a>;
     type CredentialManager<'a> = crate::token::Jwt;
 
-    fn context(&self) -> Self::Context<'_> {}
+    fn context(&self) -> Self::Context<'_> {
+        RepoCtx {
+            pool: &self.pool,
+            repo: &self.repo,
+            password_policy: &self.password_policy,
+        }
+    }
     fn credential_manager(&self) -> &Self::CredentialManager<'_> {
         &self.jwt
     }
 }
 
+impl crate::entity::ProvideAuditLog for State {
+    type Context<'a> = &'a sqlx::MySqlPool;
+    type AuditLog<'a> = crate::audit::AuditLogger;
+
+    fn context(&self) -> Self::Context<'_> {
+        &self.pool
+    }
+    fn audit_log(&self) -> &Self::AuditLog<'_> {
+        &self.audit_log
+    }
+}
+
 impl crate::entity::ProvideUserRegistry for State {
     type Context<'a> = RepoCtx<'a>;
     type UserRegistry<'a> = crate::registry::Registry;
@@ -46,6 +126,7 @@ impl crate::entity::ProvideUserRegistry for State {
         RepoCtx {
             pool: &self.pool,
             repo: &self.repo,
+            password_policy: &self.password_policy,
         }
     }
     fn user_registry(&self) -> &Self::UserRegistry<'_> {
@@ -53,35 +134,136 @@ impl crate::entity::ProvideUserRegistry for State {
     }
 }
 
+impl crate::health::HealthCheck for State {
+    fn readiness(&self) -> &crate::health::Readiness {
+        &self.readiness
+    }
+
+    async fn check_readiness(&self) -> crate::health::ReadinessReport {
+        use crate::health::{ReadinessReport, check};
+
+        let database = check("database", async {
+            sqlx::query("SELECT 1").execute(&self.pool).await?;
+            Ok(())
+        });
+        let migrations = check("migrations", async {
+            let pending = self.check_schema().await?;
+            anyhow::ensure!(pending == 0, "{pending} migration(s) pending");
+            Ok(())
+        });
+        let signing_key = check("signing_key", async { self.jwt.check_signing_key() });
+        let (database, migrations, signing_key) = tokio::join!(database, migrations, signing_key);
+        let checks = vec![database, migrations, signing_key];
+        ReadinessReport::new(self.readiness.is_draining(), checks)
+    }
+}
+
 impl State {
     pub fn new(init: StateInit) -> Self {
         let StateInit {
             cookie_name,
             path_prefix,
+            cookie_policy,
+            trusted_origins,
+            cors,
+            limits,
+            trusted_proxies,
+            assets,
+            security,
             pool,
             repo,
+            auto_migrate,
             jwt,
+            audit_log,
+            password_policy,
+            bootstrap_admin,
         } = init;
         let registry = crate::registry::Registry::new();
+        crate::metrics::watch_pool(&pool);
         Self {
             cookie_name,
             path_prefix,
+            cookie_policy,
+            trusted_origins,
+            cors,
+            limits,
+            trusted_proxies,
+            assets,
+            security,
             pool,
             repo,
+            auto_migrate,
             jwt,
+            audit_log,
+            password_policy,
             registry,
+            bootstrap_admin,
+            readiness: crate::health::Readiness::new(),
         }
     }
 
     pub async fn setup(&self) -> anyhow::Result<()> {
+        if self.auto_migrate {
+            self.migrate().await?;
+        } else {
+            let pending = self.check_schema().await?;
+            anyhow::ensure!(
+                pending == 0,
+                "{pending} migration(s) pending and auto-migrate is disabled; run `migrate` first"
+            );
+        }
+        if let Some(admin) = &self.bootstrap_admin {
+            self.bootstrap_admin(admin).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn migrate(&self) -> anyhow::Result<()> {
         self.repo.migrate(&self.pool).await
     }
+
+    /// See [`crate::Repository::check_schema`].
+    pub async fn check_schema(&self) -> anyhow::Result<usize> {
+        self.repo.check_schema(&self.pool).await
+    }
+
+    pub async fn migration_status(&self) -> anyhow::Result<Vec<crate::MigrationStatus>> {
+        self.repo.migration_status(&self.pool).await
+    }
+
+    #[tracing::instrument(skip_all, fields(display_id = %admin.display_id))]
+    async fn bootstrap_admin(&self, admin: &BootstrapAdmin) -> Result<(), Failure> {
+        use crate::entity::ProvideUserRegistry;
+        use crate::error::RejectKind;
+
+        let params = crate::entity::GetUserParams::ByDisplayId(admin.display_id.clone());
+        let user = match self.get_user(params).await {
+            Ok(user) => user,
+            Err(Failure::Reject(r)) if r.kind() == RejectKind::NotFound => {
+                let params = crate::entity::RegisterUserParams {
+                    display_id: admin.display_id.clone(),
+                    name: admin.name.clone(),
+                    raw_password: admin.raw_password.clone(),
+                };
+                let user = self.register_user(params).await?;
+                tracing::info!("Registered bootstrap admin");
+                user
+            }
+            Err(e) => return Err(e),
+        };
+        let params = crate::entity::GrantRoleParams {
+            user_id: user.id,
+            role: crate::entity::Role::ADMIN.to_string(),
+        };
+        self.grant_role(params).await
+    }
 }
 
 #[derive(Clone)]
 pub struct RepoCtx<'a> {
     pool: &'a sqlx::MySqlPool,
     repo: &'a crate::repository::Repository,
+    password_policy: &'a crate::password::Policy,
 }
 
 impl crate::entity::ProvideUserRepository for RepoCtx<'_> {
@@ -119,3 +301,55 @@ impl crate::entity::ProvideUserPasswordRepository for RepoCtx<'_> {
         self.repo
     }
 }
+
+impl crate::entity::ProvideRoleRepository for RepoCtx<'_> {
+    type Context<'b>
+        = &'b sqlx::MySqlPool
+    where
+        Self: 'b;
+    type RoleRepository<'b>
+        = crate::repository::Repository
+    where
+        Self: 'b;
+
+    fn context(&self) -> Self::Context<'_> {
+        self.pool
+    }
+    fn role_repository(&self) -> &Self::RoleRepository<'_> {
+        self.repo
+    }
+}
+
+impl crate::entity::ProvideSessionRepository for RepoCtx<'_> {
+    type Context<'b>
+        = &'b sqlx::MySqlPool
+    where
+        Self: 'b;
+    type SessionRepository<'b>
+        = crate::repository::Repository
+    where
+        Self: 'b;
+
+    fn context(&self) -> Self::Context<'_> {
+        self.pool
+    }
+    fn session_repository(&self) -> &Self::SessionRepository<'_> {
+        self.repo
+    }
+}
+
+impl crate::entity::ProvidePasswordPolicy for RepoCtx<'_> {
+    type Context<'b>
+        = ()
+    where
+        Self: 'b;
+    type PasswordPolicy<'b>
+        = crate::password::Policy
+    where
+        Self: 'b;
+
+    fn context(&self) -> Self::Context<'_> {}
+    fn password_policy(&self) -> &Self::PasswordPolicy<'_> {
+        self.password_policy
+    }
+}
@@ -0,0 +1,359 @@
+//! Trusted reverse proxies, and the PROXY protocol they may use to pass on client addresses.
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// An address range like `10.0.0.0/8`, or a single address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    #[must_use]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.split_once('/').map_or((s, None), |(a, p)| (a, Some(p)));
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| anyhow::anyhow!("expected an IP address or range like 10.0.0.0/8"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| anyhow::anyhow!("expected a prefix length up to {max}"))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// The proxies whose forwarding headers and PROXY protocol headers are believed.
+#[must_use]
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+    unix_socket: bool,
+}
+
+impl TrustedProxies {
+    pub fn new(nets: Vec<IpNet>) -> Self {
+        Self {
+            nets,
+            unix_socket: false,
+        }
+    }
+
+    /// Also believes peers on a Unix socket, which have no address to match.
+    pub fn trust_unix_socket(self, unix_socket: bool) -> Self {
+        Self {
+            unix_socket,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.nets.iter().any(|net| net.contains(ip))
+    }
+
+    #[must_use]
+    pub fn trusts_unix_socket(&self) -> bool {
+        self.unix_socket
+    }
+}
+
+/// The longest PROXY protocol v1 header, including its CRLF.
+const V1_MAX_LEN: usize = 107;
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("PROXY protocol: {message}"),
+    )
+}
+
+/// Reads the PROXY protocol v1 or v2 header a connection from `peer` must start with, and
+/// returns the client address it carries.
+///
+/// Headers from untrusted peers are refused, and reads stop exactly at the end of the header so
+/// that the stream can be handed on as is.
+pub async fn read_header<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    peer: SocketAddr,
+    trusted: &TrustedProxies,
+) -> io::Result<SocketAddr> {
+    if !trusted.contains(peer.ip()) {
+        return Err(invalid("header from an untrusted peer"));
+    }
+    let mut start = [0; 5];
+    stream.read_exact(&mut start).await?;
+    let client = match &start {
+        b"PROXY" => read_v1(stream).await?,
+        start if start[..] == V2_SIGNATURE[..5] => read_v2(stream).await?,
+        _ => return Err(invalid("missing header")),
+    };
+    // `UNKNOWN` and `LOCAL` connections, such as health checks, come from the proxy itself
+    Ok(client.unwrap_or(peer))
+}
+
+async fn read_v1<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    // read byte by byte, as buffering could consume the start of the request
+    let mut line = b"PROXY".to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() == V1_MAX_LEN {
+            return Err(invalid("v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2]).map_err(|_| invalid("not ASCII"))?;
+    let parts: Vec<_> = line.split(' ').collect();
+    match parts[..] {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", src, _dst, sport, _dport] => {
+            let ip = src.parse().map_err(|_| invalid("bad source address"))?;
+            let port = sport.parse().map_err(|_| invalid("bad source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("malformed v1 header")),
+    }
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    let mut header = [0; 11];
+    stream.read_exact(&mut header).await?;
+    if header[..7] != V2_SIGNATURE[5..] {
+        return Err(invalid("bad v2 signature"));
+    }
+    let [version_command, family, len_hi, len_lo] = header[7..] else {
+        unreachable!("four bytes remain");
+    };
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported version"));
+    }
+    let mut payload = vec![0; usize::from(u16::from_be_bytes([len_hi, len_lo]))];
+    stream.read_exact(&mut payload).await?;
+    match version_command & 0x0f {
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("unsupported command")),
+    }
+    // the source address and port lead the payload; TLVs may follow
+    let short = || invalid("v2 address block too short");
+    let client = match family >> 4 {
+        1 => {
+            let block = payload.get(..12).ok_or_else(short)?;
+            let ip: [u8; 4] = block[..4].try_into().expect("four bytes");
+            let port = u16::from_be_bytes([block[8], block[9]]);
+            SocketAddr::new(Ipv4Addr::from(ip).into(), port)
+        }
+        2 => {
+            let block = payload.get(..36).ok_or_else(short)?;
+            let ip: [u8; 16] = block[..16].try_into().expect("sixteen bytes");
+            let port = u16::from_be_bytes([block[32], block[33]]);
+            SocketAddr::new(Ipv6Addr::from(ip).into(), port)
+        }
+        // unspecified or Unix socket addresses
+        _ => return Ok(None),
+    };
+    Ok(Some(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: &str = "10.0.0.1:5000";
+
+    /// A v2 header with the given version and command byte, address family byte and payload.
+    fn v2(version_command: u8, family: u8, payload: &[u8]) -> Vec<u8> {
+        let len = u16::try_from(payload.len()).expect("short payload");
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([version_command, family]);
+        header.extend(len.to_be_bytes());
+        header.extend(payload);
+        header
+    }
+
+    fn v2_tcp4(src: [u8; 4], port: u16) -> Vec<u8> {
+        let mut payload = src.to_vec();
+        payload.extend([192, 0, 2, 9]);
+        payload.extend(port.to_be_bytes());
+        payload.extend(443u16.to_be_bytes());
+        payload
+    }
+
+    fn v2_tcp6(src: Ipv6Addr, port: u16) -> Vec<u8> {
+        let mut payload = src.octets().to_vec();
+        payload.extend(Ipv6Addr::LOCALHOST.octets());
+        payload.extend(port.to_be_bytes());
+        payload.extend(443u16.to_be_bytes());
+        payload
+    }
+
+    async fn read(mut input: &[u8], peer: &str) -> io::Result<(SocketAddr, Vec<u8>)> {
+        let trusted = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]);
+        let client = read_header(&mut input, peer.parse().unwrap(), &trusted).await?;
+        Ok((client, input.to_vec()))
+    }
+
+    #[tokio::test]
+    async fn read_header_cases() {
+        let tcp4 = v2_tcp4([192, 0, 2, 1], 1234);
+        let tcp6 = v2_tcp6("2001:db8::1".parse().unwrap(), 1234);
+        let mut with_tlv = tcp4.clone();
+        with_tlv.extend([0x04, 0x00, 0x01, 0xff]);
+        let too_long = format!("PROXY TCP4 {} 192.0.2.9 1234 443\r\n", "1".repeat(100));
+        let cases: &[(&str, Vec<u8>, Option<&str>)] = &[
+            (
+                "v1 tcp4",
+                b"PROXY TCP4 192.0.2.1 192.0.2.9 1234 443\r\n".to_vec(),
+                Some("192.0.2.1:1234"),
+            ),
+            (
+                "v1 tcp6",
+                b"PROXY TCP6 2001:db8::1 ::1 1234 443\r\n".to_vec(),
+                Some("[2001:db8::1]:1234"),
+            ),
+            ("v1 unknown", b"PROXY UNKNOWN\r\n".to_vec(), Some(PEER)),
+            (
+                "v1 without CRLF",
+                b"PROXY TCP4 192.0.2.1 192.0.2.9 1234 443".to_vec(),
+                None,
+            ),
+            ("v1 too long", too_long.into_bytes(), None),
+            (
+                "v1 bad address",
+                b"PROXY TCP4 192.0.2 192.0.2.9 1234 443\r\n".to_vec(),
+                None,
+            ),
+            (
+                "v1 bad port",
+                b"PROXY TCP4 192.0.2.1 192.0.2.9 99999 443\r\n".to_vec(),
+                None,
+            ),
+            (
+                "v1 missing fields",
+                b"PROXY TCP4 192.0.2.1\r\n".to_vec(),
+                None,
+            ),
+            ("v2 tcp4", v2(0x21, 0x11, &tcp4), Some("192.0.2.1:1234")),
+            (
+                "v2 tcp4 with a TLV",
+                v2(0x21, 0x11, &with_tlv),
+                Some("192.0.2.1:1234"),
+            ),
+            ("v2 tcp6", v2(0x21, 0x21, &tcp6), Some("[2001:db8::1]:1234")),
+            ("v2 local", v2(0x20, 0x00, &[]), Some(PEER)),
+            (
+                "v2 local with an address",
+                v2(0x20, 0x11, &tcp4),
+                Some(PEER),
+            ),
+            ("v2 unix socket", v2(0x21, 0x31, &[0; 216]), Some(PEER)),
+            ("v2 short tcp4 block", v2(0x21, 0x11, &tcp4[..8]), None),
+            ("v2 short tcp6 block", v2(0x21, 0x21, &tcp4), None),
+            (
+                "v2 truncated payload",
+                v2(0x21, 0x11, &tcp4)[..20].to_vec(),
+                None,
+            ),
+            ("v2 truncated header", V2_SIGNATURE[..10].to_vec(), None),
+            ("v2 version 1", v2(0x11, 0x11, &tcp4), None),
+            ("v2 unknown command", v2(0x22, 0x11, &tcp4), None),
+            (
+                "v2 bad signature",
+                {
+                    let mut header = v2(0x21, 0x11, &tcp4);
+                    header[8] = b'X';
+                    header
+                },
+                None,
+            ),
+            ("no header", b"GET / HTTP/1.1\r\n".to_vec(), None),
+            ("empty", Vec::new(), None),
+        ];
+        for (name, input, expected) in cases {
+            let client = read(input, PEER).await.map(|(client, _)| client).ok();
+            let expected = expected.map(|e| e.parse().unwrap());
+            assert_eq!(client, expected, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn read_header_refuses_untrusted_peers() {
+        let input = b"PROXY TCP4 192.0.2.1 192.0.2.9 1234 443\r\n";
+        assert!(read(input, "192.0.2.7:5000").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn read_header_stops_at_the_end_of_the_header() {
+        let request = b"GET / HTTP/1.1\r\n\r\n";
+        for mut input in [
+            b"PROXY TCP4 192.0.2.1 192.0.2.9 1234 443\r\n".to_vec(),
+            v2(0x21, 0x11, &v2_tcp4([192, 0, 2, 1], 1234)),
+        ] {
+            input.extend(request);
+            let (_, rest) = read(&input, PEER).await.unwrap();
+            assert_eq!(rest, request);
+        }
+    }
+
+    #[test]
+    fn ip_net_contains() {
+        let cases = [
+            ("10.0.0.0/8", "10.255.0.1", true),
+            ("10.0.0.0/8", "11.0.0.1", false),
+            ("192.0.2.1", "192.0.2.1", true),
+            ("192.0.2.1", "192.0.2.2", false),
+            ("0.0.0.0/0", "203.0.113.5", true),
+            ("10.0.0.0/8", "::ffff:10.0.0.1", true),
+            ("2001:db8::/32", "2001:db8:1::1", true),
+            ("2001:db8::/32", "2001:db9::1", false),
+            ("10.0.0.0/8", "2001:db8::1", false),
+        ];
+        for (net, ip, expected) in cases {
+            let net: IpNet = net.parse().unwrap();
+            assert_eq!(net.contains(ip.parse().unwrap()), expected, "{net} {ip}");
+        }
+        for bad in ["10.0.0.0/33", "::/129", "10.0.0/8", "10.0.0.0/x"] {
+            assert!(bad.parse::<IpNet>().is_err(), "{bad}");
+        }
+    }
+}
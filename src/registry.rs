@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::entity;
 use crate::error::Failure;
 
@@ -16,8 +18,12 @@ impl Registry {
 
 impl<Context> entity::UserRegistry<Context> for Registry
 where
-    Context: entity::ProvideUserRepository + entity::ProvideUserPasswordRepository,
+    Context: entity::ProvideUserRepository
+        + entity::ProvideUserPasswordRepository
+        + entity::ProvideRoleRepository
+        + entity::ProvidePasswordPolicy,
 {
+    #[tracing::instrument(skip_all)]
     async fn get_user(
         &self,
         ctx: Context,
@@ -26,10 +32,16 @@ where
         ctx.get_user(params).await
     }
 
-    async fn get_users(&self, ctx: Context) -> Result<Vec<entity::User>, Failure> {
-        ctx.get_users().await
+    #[tracing::instrument(skip_all, fields(search = ?params.search, limit = params.limit))]
+    async fn get_users(
+        &self,
+        ctx: Context,
+        params: entity::GetUsersParams,
+    ) -> Result<entity::UserPage, Failure> {
+        ctx.get_users(params).await
     }
 
+    #[tracing::instrument(skip_all, fields(display_id = %params.display_id))]
     async fn register_user(
         &self,
         ctx: Context,
@@ -40,16 +52,68 @@ where
             name,
             raw_password: raw,
         } = params;
-        let params = entity::CreateUserParams { display_id, name };
-        let user = ctx.create_user(params).await?;
-        let params = entity::SaveUserPasswordParams {
-            user_id: user.id,
-            raw,
+        let params = entity::CheckPasswordParams { raw: raw.clone() };
+        ctx.check_password(params).await?;
+        let params = entity::CreateUserParams {
+            display_id,
+            name,
+            raw_password: raw,
         };
-        ctx.save_user_password(params).await?;
-        Ok(user)
+        ctx.create_user(params).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.id.0))]
+    async fn update_user(
+        &self,
+        ctx: Context,
+        params: entity::UpdateUserParams,
+    ) -> Result<entity::User, Failure> {
+        if params.display_id.is_none() && params.name.is_none() {
+            return Err(Failure::bad_request("Nothing to update"));
+        }
+        ctx.update_user(params).await
     }
 
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0))]
+    async fn delete_user(
+        &self,
+        ctx: Context,
+        params: entity::DeleteUserParams,
+    ) -> Result<(), Failure> {
+        let entity::DeleteUserParams {
+            user_id,
+            raw_password: raw,
+        } = params;
+        let params = entity::VerifyUserPasswordParams { user_id, raw };
+        if !ctx.verify_user_password(params).await? {
+            return Err(Failure::unauthorized("Password mismatch"));
+        }
+        ctx.delete_user(user_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.id.0, status = params.status.as_str()))]
+    async fn set_user_status(
+        &self,
+        ctx: Context,
+        params: entity::SetUserStatusParams,
+    ) -> Result<entity::User, Failure> {
+        if params.status == entity::UserStatus::Deleted {
+            ctx.delete_user(params.id).await?;
+            return ctx.get_user(entity::GetUserParams::ById(params.id)).await;
+        }
+        ctx.set_user_status(params).await
+    }
+
+    #[tracing::instrument(skip_all, fields(%deleted_before))]
+    async fn purge_deleted_users(
+        &self,
+        ctx: Context,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, Failure> {
+        ctx.purge_deleted_users(deleted_before).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0))]
     async fn update_user_password(
         &self,
         ctx: Context,
@@ -59,10 +123,13 @@ where
             user_id,
             new_raw: raw,
         } = params;
+        let params = entity::CheckPasswordParams { raw: raw.clone() };
+        ctx.check_password(params).await?;
         let params = entity::SaveUserPasswordParams { user_id, raw };
         ctx.save_user_password(params).await
     }
 
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0))]
     async fn verify_user_password(
         &self,
         ctx: Context,
@@ -70,4 +137,92 @@ where
     ) -> Result<bool, Failure> {
         ctx.verify_user_password(params).await
     }
+
+    #[tracing::instrument(skip_all, fields(display_id = %params.display_id))]
+    async fn authenticate_user(
+        &self,
+        ctx: Context,
+        params: entity::AuthenticateUserParams,
+    ) -> Result<entity::User, Failure> {
+        use crate::error::RejectKind;
+
+        let entity::AuthenticateUserParams {
+            display_id,
+            raw_password,
+        } = params;
+        let params = entity::GetUserParams::ByDisplayId(display_id);
+        let user = match ctx.get_user(params).await {
+            Ok(user) => Some(user),
+            Err(Failure::Reject(r)) if r.kind() == RejectKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        // an unknown user is still verified, against an id that never has a password
+        let user_id = user
+            .as_ref()
+            .map_or(entity::UserId(uuid::Uuid::nil()), |u| u.id);
+        let params = entity::VerifyUserPasswordParams {
+            user_id,
+            raw: raw_password,
+        };
+        let matches = ctx.verify_user_password(params).await?;
+        let Some(user) = user else {
+            return Err(Failure::not_found("User not found"));
+        };
+        if !matches {
+            return Err(Failure::unauthorized("Password mismatch"));
+        }
+        user.status.ensure_active()?;
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %user_id.0))]
+    async fn get_user_roles(
+        &self,
+        ctx: Context,
+        user_id: entity::UserId,
+    ) -> Result<Vec<entity::Role>, Failure> {
+        ctx.get_user_roles(user_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, role = %params.role))]
+    async fn grant_role(
+        &self,
+        ctx: Context,
+        params: entity::GrantRoleParams,
+    ) -> Result<(), Failure> {
+        ctx.grant_role(params).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, role = %params.role))]
+    async fn revoke_role(
+        &self,
+        ctx: Context,
+        params: entity::RevokeRoleParams,
+    ) -> Result<(), Failure> {
+        ctx.revoke_role(params).await
+    }
+}
+
+/// Hard-deletes users soft-deleted more than `retention` ago, every `interval`.
+pub async fn purge_deleted_users_periodically<S>(state: &S, retention: Duration, interval: Duration)
+where
+    S: entity::ProvideUserRegistry,
+{
+    let Ok(retention) = chrono::Duration::from_std(retention) else {
+        tracing::error!("Retention period out of range");
+        return;
+    };
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match state
+            .purge_deleted_users(chrono::Utc::now() - retention)
+            .await
+        {
+            Ok(0) => {}
+            Ok(purged) => tracing::info!(purged, "Purged deleted users"),
+            Err(e) => tracing::error!(error = %e, "Failed to purge deleted users"),
+        }
+    }
 }
@@ -1,25 +1,106 @@
+mod audit_events;
+mod roles;
+mod sessions;
 pub mod user_passwords;
 mod users;
 
+use anyhow::Context;
+
 #[must_use]
 #[derive(Debug, Clone)]
 pub struct Repository {
-    bcrypt_cost: u32,
+    hasher: crate::password::HashingPool,
 }
 
 impl Repository {
-    pub fn new(bcrypt_cost: u32) -> Self {
-        Self { bcrypt_cost }
+    pub fn new(hasher: crate::password::HashingPool) -> Self {
+        Self { hasher }
     }
 
+    /// Applies pending migrations after [`Self::check_schema`].
     #[tracing::instrument(skip_all)]
     pub async fn migrate(&self, pool: &sqlx::MySqlPool) -> anyhow::Result<()> {
-        use anyhow::Context;
+        self.check_schema(pool).await?;
+        MIGRATOR.run(pool).await.context("Failed to migrate")
+    }
+
+    /// Refuses a schema with migrations this binary does not know, i.e. one migrated by a newer
+    /// release, and returns how many of our migrations are still pending.
+    pub async fn check_schema(&self, pool: &sqlx::MySqlPool) -> anyhow::Result<usize> {
+        pending_migrations(&applied_versions(pool).await?)
+    }
+
+    /// Lists every known migration and whether it has been applied to `pool`.
+    pub async fn migration_status(
+        &self,
+        pool: &sqlx::MySqlPool,
+    ) -> anyhow::Result<Vec<MigrationStatus>> {
+        let applied = applied_versions(pool).await?;
+        let status = MIGRATOR
+            .iter()
+            .map(|m| MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                applied: applied.contains(&m.version),
+            })
+            .collect();
+        Ok(status)
+    }
+}
+
+/// Counts the known migrations missing from `applied`, refusing versions we do not know.
+fn pending_migrations(applied: &[i64]) -> anyhow::Result<usize> {
+    let unknown = applied
+        .iter()
+        .filter(|&&v| !MIGRATOR.iter().any(|m| m.version == v))
+        .max();
+    if let Some(unknown) = unknown {
+        let latest = MIGRATOR.iter().map(|m| m.version).max().unwrap_or_default();
+        anyhow::bail!(
+            "Database schema is at version {unknown}, newer than the latest known \
+             migration {latest}; upgrade this binary"
+        );
+    }
+    let pending = MIGRATOR
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .count();
+    Ok(pending)
+}
 
-        const MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+/// The versions successfully applied to `pool`, empty before the first migration.
+async fn applied_versions(pool: &sqlx::MySqlPool) -> anyhow::Result<Vec<i64>> {
+    let (exists,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM `information_schema`.`tables` \
+         WHERE `table_schema` = DATABASE() AND `table_name` = '_sqlx_migrations'",
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up the migrations table")?;
+    if exists == 0 {
+        return Ok(Vec::new());
+    }
+    sqlx::query_scalar("SELECT `version` FROM `_sqlx_migrations` WHERE `success`")
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch applied migrations")
+}
+
+const MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
 
-        MIGRATOR.run(pool).await.context("Failed to mgirate")
+/// Truncates `value` to at most `max` characters to fit its column.
+fn truncate(mut value: String, max: usize) -> String {
+    if let Some((i, _)) = value.char_indices().nth(max) {
+        value.truncate(i);
     }
+    value
 }
 
 pub trait AsMySqlPool: Send + Sync {
@@ -40,3 +121,23 @@ where
         T::as_mysql_pool(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_migrations_are_counted_and_newer_schemas_refused() {
+        let known: Vec<_> = MIGRATOR.iter().map(|m| m.version).collect();
+        assert_eq!(pending_migrations(&[]).unwrap(), known.len());
+        assert_eq!(pending_migrations(&known[..1]).unwrap(), known.len() - 1);
+        assert_eq!(pending_migrations(&known).unwrap(), 0);
+
+        let newer = known.iter().max().unwrap() + 1;
+        let e = pending_migrations(&[known.clone(), vec![newer]].concat()).unwrap_err();
+        assert!(
+            e.to_string().contains(&format!("at version {newer}")),
+            "{e}"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Context;
+
+use super::truncate;
+use super::users::DbUserId;
+use crate::Failure;
+use crate::entity::{AuditEvent, RecordAuditEventParams, SearchAuditEventsParams};
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct DbAuditEvent {
+    id: u64,
+    occurred_at: chrono::DateTime<chrono::Utc>,
+    kind: String,
+    actor_id: Option<DbUserId>,
+    subject_id: Option<DbUserId>,
+    detail: Option<String>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+}
+
+impl TryFrom<DbAuditEvent> for AuditEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(value: DbAuditEvent) -> Result<Self, Self::Error> {
+        let DbAuditEvent {
+            id,
+            occurred_at,
+            kind,
+            actor_id,
+            subject_id,
+            detail,
+            ip,
+            user_agent,
+            request_id,
+        } = value;
+        Ok(Self {
+            id,
+            occurred_at,
+            kind: kind.parse()?,
+            actor: actor_id.map(Into::into),
+            subject: subject_id.map(Into::into),
+            detail,
+            ip: ip.map(|ip| ip.parse()).transpose()?,
+            user_agent,
+            request_id,
+        })
+    }
+}
+
+impl<Context> crate::entity::AuditLog<Context> for super::Repository
+where
+    Context: super::AsMySqlPool,
+{
+    #[tracing::instrument(skip_all, fields(kind = params.kind.as_str()))]
+    async fn record_audit_event(
+        &self,
+        ctx: Context,
+        params: RecordAuditEventParams,
+    ) -> Result<(), Failure> {
+        let RecordAuditEventParams {
+            kind,
+            actor,
+            subject,
+            detail,
+            ip,
+            user_agent,
+            request_id,
+        } = params;
+        sqlx::query(
+            "INSERT INTO `audit_events` \
+             (`kind`, `actor_id`, `subject_id`, `detail`, `ip`, `user_agent`, `request_id`) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(kind.as_str())
+        .bind(actor.map(DbUserId::from))
+        .bind(subject.map(DbUserId::from))
+        .bind(detail.map(|d| truncate(d, 255)))
+        .bind(ip.map(|ip| ip.to_string()))
+        .bind(user_agent.map(|ua| truncate(ua, 255)))
+        .bind(request_id.map(|id| truncate(id, 64)))
+        .execute(ctx.as_mysql_pool())
+        .await
+        .context("Failed to record audit event")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn search_audit_events(
+        &self,
+        ctx: Context,
+        params: SearchAuditEventsParams,
+    ) -> Result<Vec<AuditEvent>, Failure> {
+        let SearchAuditEventsParams {
+            user_id,
+            kind,
+            since,
+            until,
+            before,
+            limit,
+        } = params;
+        let mut query = sqlx::QueryBuilder::new("SELECT * FROM `audit_events` WHERE TRUE");
+        if let Some(user_id) = user_id {
+            let user_id = DbUserId::from(user_id);
+            query
+                .push(" AND (`actor_id` = ")
+                .push_bind(user_id)
+                .push(" OR `subject_id` = ")
+                .push_bind(user_id)
+                .push(")");
+        }
+        if let Some(kind) = kind {
+            query.push(" AND `kind` = ").push_bind(kind.as_str());
+        }
+        if let Some(since) = since {
+            query.push(" AND `occurred_at` >= ").push_bind(since);
+        }
+        if let Some(until) = until {
+            query.push(" AND `occurred_at` < ").push_bind(until);
+        }
+        if let Some(before) = before {
+            query.push(" AND `id` < ").push_bind(before);
+        }
+        query
+            .push(" ORDER BY `id` DESC LIMIT ")
+            .push_bind(limit.clamp(1, SearchAuditEventsParams::MAX_LIMIT));
+        let events = query
+            .build_query_as()
+            .fetch_all(ctx.as_mysql_pool())
+            .await
+            .context("Failed to search audit events")?
+            .into_iter()
+            .map(|e: DbAuditEvent| e.try_into())
+            .collect::<anyhow::Result<_>>()?;
+        Ok(events)
+    }
+}
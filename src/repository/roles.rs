@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+
+use super::users::DbUserId;
+use crate::Failure;
+use crate::entity::{Permission, Role, UserId, UserStatus};
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct DbRolePermission {
+    role: String,
+    permission: Option<String>,
+}
+
+fn collect_roles(rows: Vec<DbRolePermission>) -> Vec<Role> {
+    let mut roles: BTreeMap<String, Vec<Permission>> = BTreeMap::new();
+    for DbRolePermission { role, permission } in rows {
+        let permissions = roles.entry(role).or_default();
+        permissions.extend(permission.map(Permission));
+    }
+    roles
+        .into_iter()
+        .map(|(name, permissions)| Role { name, permissions })
+        .collect()
+}
+
+impl<Context> crate::entity::RoleRepository<Context> for super::Repository
+where
+    Context: super::AsMySqlPool,
+{
+    #[tracing::instrument(skip_all)]
+    async fn get_roles(&self, ctx: Context) -> Result<Vec<Role>, Failure> {
+        let rows = sqlx::query_as(
+            "SELECT `roles`.`name` AS `role`, `role_permissions`.`permission` FROM `roles` \
+             LEFT JOIN `role_permissions` ON `role_permissions`.`role` = `roles`.`name`",
+        )
+        .fetch_all(ctx.as_mysql_pool())
+        .await
+        .context("Failed to fetch roles")?;
+        Ok(collect_roles(rows))
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %user_id.0))]
+    async fn get_user_roles(&self, ctx: Context, user_id: UserId) -> Result<Vec<Role>, Failure> {
+        let rows = sqlx::query_as(
+            "SELECT `user_roles`.`role`, `role_permissions`.`permission` FROM `user_roles` \
+             LEFT JOIN `role_permissions` ON `role_permissions`.`role` = `user_roles`.`role` \
+             WHERE `user_roles`.`user_id` = ?",
+        )
+        .bind(DbUserId::from(user_id))
+        .fetch_all(ctx.as_mysql_pool())
+        .await
+        .context("Failed to fetch user roles")?;
+        Ok(collect_roles(rows))
+    }
+
+    #[tracing::instrument(skip_all, fields(role = %params.name))]
+    async fn create_role(
+        &self,
+        ctx: Context,
+        params: crate::entity::CreateRoleParams,
+    ) -> Result<Role, Failure> {
+        let crate::entity::CreateRoleParams { name, permissions } = params;
+        let mut tx = ctx
+            .as_mysql_pool()
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+        let inserted = sqlx::query("INSERT IGNORE INTO `roles` (`name`) VALUES (?)")
+            .bind(&name)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create role")?
+            .rows_affected();
+        if inserted == 0 {
+            return Err(Failure::conflict(
+                "A role with the same name already exists",
+            ));
+        }
+        for Permission(permission) in &permissions {
+            sqlx::query(
+                "INSERT IGNORE INTO `role_permissions` (`role`, `permission`) VALUES (?, ?)",
+            )
+            .bind(&name)
+            .bind(permission)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to create role permission")?;
+        }
+        tx.commit().await.context("Failed to commit role")?;
+        Ok(Role { name, permissions })
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, role = %params.role))]
+    async fn grant_role(
+        &self,
+        ctx: Context,
+        params: crate::entity::GrantRoleParams,
+    ) -> Result<(), Failure> {
+        let pool = ctx.as_mysql_pool();
+        let exists: Option<(String,)> =
+            sqlx::query_as("SELECT `name` FROM `roles` WHERE `name` = ?")
+                .bind(&params.role)
+                .fetch_optional(pool)
+                .await
+                .context("Failed to fetch role")?;
+        if exists.is_none() {
+            return Err(Failure::not_found("Role not found"));
+        }
+        let user_id = DbUserId::from(params.user_id);
+        let status: Option<(String,)> =
+            sqlx::query_as("SELECT `status` FROM `users` WHERE `id` = ?")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await
+                .context("Failed to fetch user")?;
+        if status.is_none_or(|(status,)| status == UserStatus::Deleted.as_str()) {
+            return Err(Failure::not_found("User not found"));
+        }
+        // granting a role twice is fine; any other failure, such as the user being purged
+        // meanwhile, is not
+        sqlx::query(
+            "INSERT INTO `user_roles` (`user_id`, `role`) VALUES (?, ?) \
+             ON DUPLICATE KEY UPDATE `role` = `role`",
+        )
+        .bind(user_id)
+        .bind(params.role)
+        .execute(pool)
+        .await
+        .context("Failed to grant role")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, role = %params.role))]
+    async fn revoke_role(
+        &self,
+        ctx: Context,
+        params: crate::entity::RevokeRoleParams,
+    ) -> Result<(), Failure> {
+        sqlx::query("DELETE FROM `user_roles` WHERE `user_id` = ? AND `role` = ?")
+            .bind(DbUserId::from(params.user_id))
+            .bind(params.role)
+            .execute(ctx.as_mysql_pool())
+            .await
+            .context("Failed to revoke role")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::Context;
+
+use super::truncate;
+use super::users::DbUserId;
+use crate::Failure;
+use crate::entity::{Session, SessionId, SessionWithUserStatus, UserId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(transparent)]
+struct DbSessionId(uuid::Uuid);
+
+impl From<SessionId> for DbSessionId {
+    fn from(value: SessionId) -> Self {
+        Self(value.0)
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct DbSession {
+    id: DbSessionId,
+    user_id: DbUserId,
+    user_agent: Option<String>,
+    ip: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    last_used_at: chrono::DateTime<chrono::Utc>,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct DbSessionWithUserStatus {
+    #[sqlx(flatten)]
+    session: DbSession,
+    user_status: String,
+}
+
+impl TryFrom<DbSession> for Session {
+    type Error = anyhow::Error;
+
+    fn try_from(value: DbSession) -> Result<Self, Self::Error> {
+        let DbSession {
+            id,
+            user_id,
+            user_agent,
+            ip,
+            created_at,
+            last_used_at,
+            expires_at,
+        } = value;
+        Ok(Self {
+            id: SessionId(id.0),
+            user_id: user_id.into(),
+            user_agent,
+            ip: ip.map(|ip| ip.parse()).transpose()?,
+            created_at,
+            last_used_at,
+            expires_at,
+        })
+    }
+}
+
+impl<Context> crate::entity::SessionRepository<Context> for super::Repository
+where
+    Context: super::AsMySqlPool,
+{
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0))]
+    async fn create_session(
+        &self,
+        ctx: Context,
+        params: crate::entity::CreateSessionParams,
+    ) -> Result<Session, Failure> {
+        let crate::entity::CreateSessionParams {
+            user_id,
+            user_agent,
+            ip,
+            expires_at,
+        } = params;
+        let pool = ctx.as_mysql_pool();
+        let user_id = DbUserId::from(user_id);
+        sqlx::query("DELETE FROM `sessions` WHERE `user_id` = ? AND `expires_at` < ?")
+            .bind(user_id)
+            .bind(chrono::Utc::now())
+            .execute(pool)
+            .await
+            .context("Failed to delete expired sessions")?;
+        let id = SessionId(uuid::Uuid::new_v4());
+        sqlx::query(
+            "INSERT INTO `sessions` (`id`, `user_id`, `user_agent`, `ip`, `expires_at`) \
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(DbSessionId::from(id))
+        .bind(user_id)
+        .bind(user_agent.map(|ua| truncate(ua, 255)))
+        .bind(ip.map(|ip| ip.to_string()))
+        .bind(expires_at)
+        .execute(pool)
+        .await
+        .context("Failed to create session")?;
+        self.get_session(ctx, id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %id.0))]
+    async fn get_session(&self, ctx: Context, id: SessionId) -> Result<Session, Failure> {
+        let session: DbSession = sqlx::query_as("SELECT * FROM `sessions` WHERE `id` = ?")
+            .bind(DbSessionId::from(id))
+            .fetch_optional(ctx.as_mysql_pool())
+            .await
+            .context("Failed to fetch session")?
+            .ok_or_else(|| Failure::not_found("Session not found"))?;
+        Ok(session.try_into()?)
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %id.0))]
+    async fn get_session_with_user_status(
+        &self,
+        ctx: Context,
+        id: SessionId,
+    ) -> Result<SessionWithUserStatus, Failure> {
+        let row: DbSessionWithUserStatus = sqlx::query_as(
+            "SELECT `sessions`.*, `users`.`status` AS `user_status` FROM `sessions` \
+             JOIN `users` ON `users`.`id` = `sessions`.`user_id` WHERE `sessions`.`id` = ?",
+        )
+        .bind(DbSessionId::from(id))
+        .fetch_optional(ctx.as_mysql_pool())
+        .await
+        .context("Failed to fetch session")?
+        .ok_or_else(|| Failure::not_found("Session not found"))?;
+        Ok(SessionWithUserStatus {
+            session: row.session.try_into()?,
+            user_status: row.user_status.parse()?,
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %user_id.0))]
+    async fn get_user_sessions(
+        &self,
+        ctx: Context,
+        user_id: UserId,
+    ) -> Result<Vec<Session>, Failure> {
+        let sessions = sqlx::query_as(
+            "SELECT * FROM `sessions` WHERE `user_id` = ? AND `expires_at` > ? \
+             ORDER BY `last_used_at` DESC",
+        )
+        .bind(DbUserId::from(user_id))
+        .bind(chrono::Utc::now())
+        .fetch_all(ctx.as_mysql_pool())
+        .await
+        .context("Failed to fetch user sessions")?
+        .into_iter()
+        .map(|s: DbSession| s.try_into())
+        .collect::<anyhow::Result<_>>()?;
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(skip_all, fields(session_id = %params.id.0))]
+    async fn touch_session(
+        &self,
+        ctx: Context,
+        params: crate::entity::TouchSessionParams,
+    ) -> Result<(), Failure> {
+        sqlx::query("UPDATE `sessions` SET `last_used_at` = ? WHERE `id` = ?")
+            .bind(params.used_at)
+            .bind(DbSessionId::from(params.id))
+            .execute(ctx.as_mysql_pool())
+            .await
+            .context("Failed to touch session")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, session_id = %params.id.0))]
+    async fn delete_session(
+        &self,
+        ctx: Context,
+        params: crate::entity::DeleteSessionParams,
+    ) -> Result<(), Failure> {
+        let deleted = sqlx::query("DELETE FROM `sessions` WHERE `id` = ? AND `user_id` = ?")
+            .bind(DbSessionId::from(params.id))
+            .bind(DbUserId::from(params.user_id))
+            .execute(ctx.as_mysql_pool())
+            .await
+            .context("Failed to delete session")?
+            .rows_affected();
+        if deleted == 0 {
+            return Err(Failure::not_found("Session not found"));
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %user_id.0))]
+    async fn delete_user_sessions(&self, ctx: Context, user_id: UserId) -> Result<u64, Failure> {
+        let deleted = sqlx::query("DELETE FROM `sessions` WHERE `user_id` = ?")
+            .bind(DbUserId::from(user_id))
+            .execute(ctx.as_mysql_pool())
+            .await
+            .context("Failed to delete user sessions")?
+            .rows_affected();
+        Ok(deleted)
+    }
+}
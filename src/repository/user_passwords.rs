@@ -18,39 +18,79 @@ impl<Context> crate::entity::UserPasswordRepository<Context> for super::Reposito
 where
     Context: super::AsMySqlPool,
 {
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0))]
     async fn save_user_password(
         &self,
         ctx: Context,
         params: crate::entity::SaveUserPasswordParams,
     ) -> Result<(), Failure> {
-        let psk = bcrypt::hash(params.raw, self.bcrypt_cost).context("Failed to hash password")?;
+        let psk = self.hasher.hash(params.raw).await?;
         let password = DbUserPassword {
             id: params.user_id.into(),
             psk: DbPsk(psk),
         };
-        sqlx::query("INSERT INTO `user_passwords` (`user_id`, `psk`) VALUES (?, ?)")
-            .bind(password.id)
-            .bind(password.psk)
-            .execute(ctx.as_mysql_pool())
-            .await
-            .context("Failed to insert user password")?;
+        sqlx::query(
+            "INSERT INTO `user_passwords` (`user_id`, `psk`) VALUES (?, ?) \
+             ON DUPLICATE KEY UPDATE `psk` = VALUES(`psk`)",
+        )
+        .bind(password.id)
+        .bind(password.psk)
+        .execute(ctx.as_mysql_pool())
+        .await
+        .context("Failed to insert user password")?;
         Ok(())
     }
 
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0))]
     async fn verify_user_password(
         &self,
         ctx: Context,
         params: crate::entity::VerifyUserPasswordParams,
     ) -> Result<bool, Failure> {
-        let DbPsk(psk) = sqlx::query_as("SELECT * FROM `user_passwords` WHERE `user_id` = ?")
-            .bind(DbUserId::from(params.user_id))
-            .fetch_optional(ctx.as_mysql_pool())
+        let pool = ctx.as_mysql_pool();
+        let user_id = DbUserId::from(params.user_id);
+        let raw = params.raw;
+        let psk = sqlx::query_as("SELECT * FROM `user_passwords` WHERE `user_id` = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
             .await
             .context("Failed to get user password")?
-            .map(|p: DbUserPassword| p.psk)
-            .ok_or_else(|| Failure::not_found("password not found"))?;
-        // TODO: log if err
-        let res = bcrypt::verify(params.raw, &psk).context("Failed to challenge bcrypt hash")?;
-        Ok(res)
+            .map(|p: DbUserPassword| p.psk);
+        let Some(DbPsk(psk)) = psk else {
+            // no password matches, but take as long as a mismatch would
+            self.hasher.verify_dummy(raw).await?;
+            return Ok(false);
+        };
+        let verification = self.hasher.verify(raw.clone(), psk.clone()).await?;
+        if verification.matches && verification.needs_rehash {
+            // best effort; the login itself has already succeeded
+            if let Err(e) = self.rehash_user_password(pool, user_id, &psk, raw).await {
+                tracing::warn!(error = ?e, "Failed to upgrade password hash");
+            }
+        }
+        Ok(verification.matches)
+    }
+}
+
+impl super::Repository {
+    /// Replaces `old` with a hash by the preferred algorithm, unless it has changed meanwhile.
+    #[tracing::instrument(skip_all)]
+    async fn rehash_user_password(
+        &self,
+        pool: &sqlx::MySqlPool,
+        user_id: DbUserId,
+        old: &str,
+        raw: crate::Secret,
+    ) -> Result<(), Failure> {
+        let psk = self.hasher.hash(raw).await?;
+        sqlx::query("UPDATE `user_passwords` SET `psk` = ? WHERE `user_id` = ? AND `psk` = ?")
+            .bind(DbPsk(psk))
+            .bind(user_id)
+            .bind(old)
+            .execute(pool)
+            .await
+            .context("Failed to update password hash")?;
+        tracing::debug!("Upgraded password hash");
+        Ok(())
     }
 }
@@ -1,7 +1,9 @@
 use anyhow::Context;
 
 use crate::Failure;
-use crate::entity::{User, UserId};
+use crate::entity::{
+    GetUsersParams, SortOrder, User, UserCursor, UserId, UserOrderBy, UserPage, UserStatus,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
 #[sqlx(transparent)]
@@ -24,20 +26,28 @@ pub(super) struct DbUser {
     pub(super) id: DbUserId,
     pub(super) display_id: String,
     pub(super) name: String,
+    pub(super) status: String,
+    pub(super) status_updated_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl From<DbUser> for User {
-    fn from(value: DbUser) -> Self {
+impl TryFrom<DbUser> for User {
+    type Error = anyhow::Error;
+
+    fn try_from(value: DbUser) -> Result<Self, Self::Error> {
         let DbUser {
             id,
             display_id,
             name,
+            status,
+            status_updated_at,
         } = value;
-        Self {
+        Ok(Self {
             id: id.into(),
             display_id,
             name,
-        }
+            status: status.parse()?,
+            status_updated_at,
+        })
     }
 }
 
@@ -45,17 +55,104 @@ impl<Context> crate::entity::UserRepository<Context> for super::Repository
 where
     Context: super::AsMySqlPool,
 {
-    async fn get_users(&self, ctx: Context) -> Result<Vec<User>, Failure> {
-        let users = sqlx::query_as("SELECT * FROM `users`")
-            .fetch_all(ctx.as_mysql_pool())
+    #[tracing::instrument(skip_all, fields(search = ?params.search, limit = params.limit))]
+    async fn get_users(&self, ctx: Context, params: GetUsersParams) -> Result<UserPage, Failure> {
+        let GetUsersParams {
+            search,
+            order_by,
+            order,
+            limit,
+            after,
+            include_deleted,
+        } = params;
+        let pool = ctx.as_mysql_pool();
+        let limit = limit.clamp(1, GetUsersParams::MAX_LIMIT);
+        let after = after.map(|c| c.decode(order_by)).transpose()?;
+        let filter = UserFilter {
+            pattern: search.as_deref().map(like_prefix),
+            include_deleted,
+        };
+
+        // counting every match is unbounded, so only the first page counts, and only so far
+        let total = if after.is_none() {
+            let mut count = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM (");
+            filter.push_union(&mut count, "`id`", |_| {});
+            count
+                .push(" LIMIT ")
+                .push_bind(UserPage::COUNT_LIMIT)
+                .push(") AS `matches`");
+            let (total,): (i64,) = count
+                .build_query_as()
+                .fetch_one(pool)
+                .await
+                .context("Failed to count users")?;
+            Some(total.try_into().unwrap_or_default())
+        } else {
+            None
+        };
+
+        // keyset pagination over (`order_by`, `display_id`), each branch of the search
+        // running on its own index
+        let column = match order_by {
+            UserOrderBy::DisplayId => "`display_id`",
+            UserOrderBy::Name => "`name`",
+        };
+        let (cmp, direction) = match order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        let order_clause = format!(" ORDER BY {column} {direction}, `display_id` {direction}");
+        let mut query = sqlx::QueryBuilder::new("SELECT * FROM (");
+        filter.push_union(&mut query, "*", |query| {
+            if let Some((key, display_id)) = &after {
+                query
+                    .push(format!(" AND ({column}, `display_id`) {cmp} ("))
+                    .push_bind(key.clone())
+                    .push(", ")
+                    .push_bind(display_id.clone())
+                    .push(")");
+            }
+            query
+                .push(order_clause.as_str())
+                .push(" LIMIT ")
+                .push_bind(limit + 1);
+        });
+        query
+            .push(") AS `matches`")
+            .push(order_clause.as_str())
+            .push(" LIMIT ")
+            .push_bind(limit + 1);
+        let mut users: Vec<User> = query
+            .build_query_as()
+            .fetch_all(pool)
             .await
             .context("Failed to fetch users")?
             .into_iter()
-            .map(|u: DbUser| u.into())
-            .collect();
-        Ok(users)
+            .map(|u: DbUser| u.try_into())
+            .collect::<anyhow::Result<_>>()?;
+        let next_cursor = if users.len() > limit as usize {
+            users.truncate(limit as usize);
+            users
+                .last()
+                .map(|u| {
+                    let key = match order_by {
+                        UserOrderBy::DisplayId => &u.display_id,
+                        UserOrderBy::Name => &u.name,
+                    };
+                    UserCursor::new(order_by, key, &u.display_id)
+                })
+                .transpose()?
+        } else {
+            None
+        };
+        Ok(UserPage {
+            users,
+            total,
+            next_cursor,
+        })
     }
 
+    #[tracing::instrument(skip_all)]
     async fn get_user(
         &self,
         ctx: Context,
@@ -70,6 +167,7 @@ where
         }
     }
 
+    #[tracing::instrument(skip_all, fields(display_id = %params.display_id))]
     async fn create_user(
         &self,
         ctx: Context,
@@ -77,6 +175,8 @@ where
     ) -> Result<User, Failure> {
         use crate::error::RejectKind;
 
+        User::check_display_id(&params.display_id)?;
+        User::check_name(&params.name)?;
         let pool = ctx.as_mysql_pool();
         match self.get_user_by_display_id(pool, &params.display_id).await {
             Ok(_) => {
@@ -87,21 +187,234 @@ where
             Err(Failure::Reject(r)) if r.kind() == RejectKind::NotFound => {}
             Err(e) => return Err(e),
         }
+        let crate::entity::CreateUserParams {
+            display_id,
+            name,
+            raw_password,
+        } = params;
+        // hash first: the pool may refuse or time out, and must not leave a user without a
+        // password holding on to its display id
+        let psk = self.hasher.hash(raw_password).await?;
         let id = DbUserId(uuid::Uuid::new_v4());
-        let crate::entity::CreateUserParams { display_id, name } = params;
+        let mut tx = pool.begin().await.context("Failed to begin transaction")?;
         sqlx::query("INSERT INTO `users` (`id`, `display_id`, `name`) VALUES (?, ?, ?)")
             .bind(id)
             .bind(display_id)
             .bind(name)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| match e.as_database_error() {
+                Some(d) if d.is_unique_violation() => {
+                    Failure::conflict("A user with the same display id already exists")
+                }
+                _ => anyhow::Error::new(e)
+                    .context("Failed to create user")
+                    .into(),
+            })?;
+        sqlx::query("INSERT INTO `user_passwords` (`user_id`, `psk`) VALUES (?, ?)")
+            .bind(id)
+            .bind(psk)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to insert user password")?;
+        tx.commit()
+            .await
+            .context("Failed to commit user creation")?;
+        self.get_user_by_id(pool, id.into()).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.id.0))]
+    async fn update_user(
+        &self,
+        ctx: Context,
+        params: crate::entity::UpdateUserParams,
+    ) -> Result<User, Failure> {
+        use crate::error::RejectKind;
+
+        let pool = ctx.as_mysql_pool();
+        let crate::entity::UpdateUserParams {
+            id,
+            display_id,
+            name,
+        } = params;
+        if let Some(display_id) = &display_id {
+            User::check_display_id(display_id)?;
+        }
+        if let Some(name) = &name {
+            User::check_name(name)?;
+        }
+        let current = self.get_user_by_id(pool, id).await?;
+        if let Some(display_id) = display_id.as_deref().filter(|d| *d != current.display_id) {
+            match self.get_user_by_display_id(pool, display_id).await {
+                Ok(_) => {
+                    return Err(Failure::conflict(
+                        "A user with the same display id already exists",
+                    ));
+                }
+                Err(Failure::Reject(r)) if r.kind() == RejectKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let display_id = display_id.unwrap_or(current.display_id);
+        let name = name.unwrap_or(current.name);
+        sqlx::query("UPDATE `users` SET `display_id` = ?, `name` = ? WHERE `id` = ?")
+            .bind(display_id)
+            .bind(name)
+            .bind(DbUserId::from(id))
             .execute(pool)
             .await
-            .context("Failed to create user")?;
-        let user = self.get_user_by_id(pool, id.into()).await?;
+            .map_err(|e| match e.as_database_error() {
+                Some(d) if d.is_unique_violation() => {
+                    Failure::conflict("A user with the same display id already exists")
+                }
+                _ => anyhow::Error::new(e)
+                    .context("Failed to update user")
+                    .into(),
+            })?;
+        self.get_user_by_id(pool, id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.id.0, status = params.status.as_str()))]
+    async fn set_user_status(
+        &self,
+        ctx: Context,
+        params: crate::entity::SetUserStatusParams,
+    ) -> Result<User, Failure> {
+        let pool = ctx.as_mysql_pool();
+        let crate::entity::SetUserStatusParams { id, status } = params;
+        let updated = sqlx::query(
+            "UPDATE `users` SET `status` = ?, `status_updated_at` = CURRENT_TIMESTAMP(6) \
+             WHERE `id` = ? AND `status` <> ?",
+        )
+        .bind(status.as_str())
+        .bind(DbUserId::from(id))
+        .bind(UserStatus::Deleted.as_str())
+        .execute(pool)
+        .await
+        .context("Failed to update user status")?
+        .rows_affected();
+        let user = self.get_user_by_id(pool, id).await?;
+        if updated == 0 && user.status == UserStatus::Deleted {
+            return Err(Failure::conflict("User has been deleted"));
+        }
         Ok(user)
     }
+
+    #[tracing::instrument(skip_all, fields(user_id = %id.0))]
+    async fn delete_user(&self, ctx: Context, id: UserId) -> Result<(), Failure> {
+        let id = DbUserId::from(id);
+        let mut tx = ctx
+            .as_mysql_pool()
+            .begin()
+            .await
+            .context("Failed to begin transaction")?;
+        let deleted = sqlx::query(
+            "UPDATE `users` SET `status` = ?, `status_updated_at` = CURRENT_TIMESTAMP(6) \
+             WHERE `id` = ? AND `status` <> ?",
+        )
+        .bind(UserStatus::Deleted.as_str())
+        .bind(id)
+        .bind(UserStatus::Deleted.as_str())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete user")?
+        .rows_affected();
+        if deleted == 0 {
+            return Err(Failure::not_found("User not found"));
+        }
+        for table in ["`user_passwords`", "`user_roles`", "`sessions`"] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE `user_id` = ?"))
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("Failed to delete from {table}"))?;
+        }
+        tx.commit()
+            .await
+            .context("Failed to commit user deletion")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(%deleted_before))]
+    async fn purge_deleted_users(
+        &self,
+        ctx: Context,
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, Failure> {
+        let purged =
+            sqlx::query("DELETE FROM `users` WHERE `status` = ? AND `status_updated_at` < ?")
+                .bind(UserStatus::Deleted.as_str())
+                .bind(deleted_before)
+                .execute(ctx.as_mysql_pool())
+                .await
+                .context("Failed to purge deleted users")?
+                .rows_affected();
+        Ok(purged)
+    }
+}
+
+/// Escapes LIKE wildcards in `prefix` and appends `%`.
+fn like_prefix(prefix: &str) -> String {
+    let mut pattern = String::with_capacity(prefix.len() + 1);
+    for c in prefix.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Which users a listing includes.
+struct UserFilter {
+    /// A LIKE pattern for `display_id` or `name`.
+    pattern: Option<String>,
+    include_deleted: bool,
+}
+
+impl UserFilter {
+    /// Pushes `SELECT <columns>` over the matching users, as a UNION of one branch per searched
+    /// column so that each can use its own index; `finish` adds to the conditions of a branch.
+    fn push_union(
+        &self,
+        query: &mut sqlx::QueryBuilder<'_, sqlx::MySql>,
+        columns: &str,
+        mut finish: impl FnMut(&mut sqlx::QueryBuilder<'_, sqlx::MySql>),
+    ) {
+        let searches = match &self.pattern {
+            Some(pattern) => vec![Some(("`display_id`", pattern)), Some(("`name`", pattern))],
+            None => vec![None],
+        };
+        // parenthesized only in a UNION, which older servers otherwise refuse
+        let (open, close) = if searches.len() > 1 {
+            ("(", ")")
+        } else {
+            ("", "")
+        };
+        for (i, search) in searches.into_iter().enumerate() {
+            if i > 0 {
+                query.push(" UNION ");
+            }
+            query.push(format!("{open}SELECT {columns} FROM `users` WHERE TRUE"));
+            if let Some((column, pattern)) = search {
+                query
+                    .push(format!(" AND {column} LIKE "))
+                    .push_bind(pattern.clone());
+            }
+            if !self.include_deleted {
+                query
+                    .push(" AND `status` <> ")
+                    .push_bind(UserStatus::Deleted.as_str());
+            }
+            finish(query);
+            query.push(close);
+        }
+    }
 }
 
 impl super::Repository {
+    #[tracing::instrument(skip_all, fields(user_id = %id.0))]
     async fn get_user_by_id(&self, pool: &sqlx::MySqlPool, id: UserId) -> Result<User, Failure> {
         let id = DbUserId::from(id);
         let user = sqlx::query_as::<_, DbUser>("SELECT * FROM `users` WHERE `id` = ?")
@@ -110,9 +423,10 @@ impl super::Repository {
             .await
             .context("Failed to fetch user by id")?
             .ok_or_else(|| Failure::not_found("User not found"))?;
-        Ok(user.into())
+        Ok(user.try_into()?)
     }
 
+    #[tracing::instrument(skip_all, fields(display_id = %display_id))]
     async fn get_user_by_display_id(
         &self,
         pool: &sqlx::MySqlPool,
@@ -124,6 +438,6 @@ impl super::Repository {
             .await
             .context("Failed to fetch user by display_id")?
             .ok_or_else(|| Failure::not_found("User not found"))?;
-        Ok(user.into())
+        Ok(user.try_into()?)
     }
 }
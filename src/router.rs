@@ -1,25 +1,64 @@
+mod assets;
+mod client;
+mod cookies;
+mod cors;
+mod csrf;
+mod guard;
+mod limits;
+mod request_id;
+mod security;
+
 use std::sync::Arc;
 
-use anyhow::Context;
-use axum::extract::{Form, Json, State};
-use axum::response::{IntoResponse, Redirect};
+use axum::extract::{Form, Json, Path, Query, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
 use axum_extra::extract::cookie;
 use serde::{Deserialize, Serialize};
 
-use crate::{Failure, entity};
+pub use assets::Assets;
+pub use client::{ClientInfo, TlsConnection};
+pub use cookies::CookiePolicy;
+pub use cors::{CorsPolicy, OriginPattern};
+pub use guard::{Authenticated, RequirePermission};
+pub use limits::Limits;
+pub use security::{RouteHeaders, SecurityHeaders, SecurityPolicy};
+
+use crate::{Failure, Secret, entity};
 
 pub trait RouteConfig: Send + Sync {
     fn cookie_name(&self) -> &str;
     fn path_prefix(&self) -> &str;
+    fn cookie_policy(&self) -> &CookiePolicy;
+    /// Origins besides the serving host allowed to make state-changing requests.
+    fn trusted_origins(&self) -> &[String];
+    /// Other origins allowed to call the API from a browser.
+    fn cors(&self) -> &CorsPolicy;
+    fn limits(&self) -> &Limits;
+    /// Proxies whose forwarding headers name the client; see [`ClientInfo`].
+    fn trusted_proxies(&self) -> &crate::proxy::TrustedProxies;
+    fn assets(&self) -> &Assets;
+    fn security(&self) -> &SecurityPolicy;
 }
 
 pub trait StateRequirements:
-    entity::ProvideUserRegistry + entity::ProvideCredentialManager + RouteConfig + 'static
+    entity::ProvideUserRegistry
+    + entity::ProvideCredentialManager
+    + entity::ProvideAuditLog
+    + crate::health::HealthCheck
+    + RouteConfig
+    + 'static
 {
 }
 
 impl<S> StateRequirements for S where
-    S: entity::ProvideUserRegistry + entity::ProvideCredentialManager + RouteConfig + 'static
+    S: entity::ProvideUserRegistry
+        + entity::ProvideCredentialManager
+        + entity::ProvideAuditLog
+        + crate::health::HealthCheck
+        + RouteConfig
+        + 'static
 {
 }
 
@@ -34,7 +73,8 @@ impl<S> StateRequirements for S where
 pub struct RegisterUserRequest {
     pub display_id: String,
     pub name: String,
-    pub password: String,
+    #[schema(value_type = String)]
+    pub password: Secret,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, utoipa::ToSchema)]
@@ -46,7 +86,53 @@ pub struct RegisterUserRequest {
 ))]
 pub struct LoginUserRequest {
     pub display_id: String,
-    pub password: String,
+    #[schema(value_type = String)]
+    pub password: Secret,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, utoipa::ToSchema)]
+#[schema(examples(
+    json!({
+        "name": "John Doe"
+    })
+))]
+pub struct UpdateMeRequest {
+    pub display_id: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, utoipa::ToSchema)]
+#[schema(examples(
+    json!({
+        "password": "password"
+    })
+))]
+pub struct DeleteMeRequest {
+    #[schema(value_type = String)]
+    pub password: Secret,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, utoipa::ToSchema)]
+#[schema(examples(
+    json!({
+        "current_password": "password",
+        "new_password": "correct horse battery staple"
+    })
+))]
+pub struct ChangePasswordRequest {
+    #[schema(value_type = String)]
+    pub current_password: Secret,
+    #[schema(value_type = String)]
+    pub new_password: Secret,
+}
+
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct SessionResponse {
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub session: entity::Session,
+    /// Whether this is the session making the request.
+    pub current: bool,
 }
 
 #[derive(Debug)]
@@ -64,26 +150,55 @@ impl From<anyhow::Error> for ErrorResponse {
     }
 }
 
-impl IntoResponse for ErrorResponse {
-    fn into_response(self) -> axum::response::Response {
-        use axum::http::StatusCode;
+#[derive(Serialize)]
+struct RejectBody<'a> {
+    #[serde(flatten)]
+    reject: &'a crate::error::Reject,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
 
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
         use crate::error::{Reject, RejectKind};
 
         let status_code = |r: &Reject| match r.kind() {
             RejectKind::Unauthorized => StatusCode::UNAUTHORIZED,
+            RejectKind::Forbidden => StatusCode::FORBIDDEN,
             RejectKind::BadRequest => StatusCode::BAD_REQUEST,
             RejectKind::NotFound => StatusCode::NOT_FOUND,
             RejectKind::Conflict => StatusCode::CONFLICT,
+            RejectKind::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
         };
+        let request_id = request_id::current();
         match self.0 {
             Failure::Reject(r) => {
                 tracing::info!("Reject: {r}");
-                (status_code(&r), r.message().to_string()).into_response()
+                let status = status_code(&r);
+                if r.reason().is_some() {
+                    let body = RejectBody {
+                        reject: &r,
+                        request_id,
+                    };
+                    return (status, Json(body)).into_response();
+                }
+                match request_id {
+                    Some(id) => (status, format!("{} (request id {id})", r.message())),
+                    None => (status, r.message().to_string()),
+                }
+                .into_response()
             }
             Failure::Error(e) => {
-                tracing::error!(error = ?e);
-                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                // causes may quote SQL or user input, so only the outermost context is kept at
+                // error level
+                tracing::error!(error = %e, "Internal error");
+                tracing::debug!(error = ?e, "Internal error details");
+                let status = StatusCode::INTERNAL_SERVER_ERROR;
+                match request_id {
+                    Some(id) => (status, format!("Internal server error (request id {id})")),
+                    None => (status, String::new()),
+                }
+                .into_response()
             }
         }
     }
@@ -106,11 +221,68 @@ impl<S> std::clone::Clone for AppState<S> {
 }
 
 impl<S: StateRequirements> AppState<S> {
+    /// Records an audit event, logging rather than failing the request if it cannot be stored.
+    async fn audit(
+        &self,
+        client: &ClientInfo,
+        kind: entity::AuditEventKind,
+        actor: Option<entity::UserId>,
+        subject: Option<entity::UserId>,
+        detail: Option<String>,
+    ) {
+        let ClientInfo {
+            ip,
+            proto: _,
+            user_agent,
+            request_id,
+        } = client.clone();
+        let params = entity::RecordAuditEventParams {
+            kind,
+            actor,
+            subject,
+            detail,
+            ip,
+            user_agent,
+            request_id,
+        };
+        if let Err(e) = self.record_audit_event(params).await {
+            tracing::error!(error = %e, kind = kind.as_str(), "Failed to record audit event");
+        }
+    }
+
+    fn session_cookie_name(&self) -> String {
+        self.cookie_policy().name(self.cookie_name())
+    }
+
+    /// The cookie policy for `client`, marking cookies secure whenever a trusted proxy saw HTTPS,
+    /// even if `cookie.secure` is off for plain HTTP deployments.
+    fn cookie_policy_for(&self, client: &ClientInfo) -> CookiePolicy {
+        let mut policy = self.cookie_policy().clone();
+        policy.secure |= client.is_https();
+        policy
+    }
+
+    fn session_cookie(&self, client: &ClientInfo, value: String) -> cookie::Cookie<'static> {
+        let name = self.session_cookie_name();
+        let policy = self.cookie_policy_for(client);
+        let mut cookie = policy.build(name, value, self.path_prefix());
+        cookie.set_http_only(true);
+        cookie
+    }
+
+    fn removal_cookie(&self, client: &ClientInfo) -> cookie::Cookie<'static> {
+        let name = self.session_cookie_name();
+        let policy = self.cookie_policy_for(client);
+        let mut cookie = policy.removal(name, self.path_prefix());
+        cookie.set_http_only(true);
+        cookie
+    }
+
     async fn register(
         State(state): State<Self>,
+        client: ClientInfo,
         Form(req): Form<RegisterUserRequest>,
     ) -> Result<Redirect, ErrorResponse> {
-        // TODO: validation
         let RegisterUserRequest {
             display_id,
             name,
@@ -121,103 +293,456 @@ impl<S: StateRequirements> AppState<S> {
             name,
             raw_password: password,
         };
-        let _user = state.register_user(params).await?;
+        let user = state.register_user(params).await;
+        crate::metrics::record_registration(&user);
+        let user = user?;
+        let kind = entity::AuditEventKind::Register;
+        state.audit(&client, kind, Some(user.id), None, None).await;
         let login_path = format!("{}login.html", &state.path_prefix());
         Ok(Redirect::to(&login_path))
     }
 
     async fn login(
         State(state): State<Self>,
+        client: ClientInfo,
         cookie_jar: cookie::CookieJar,
         Form(req): Form<LoginUserRequest>,
     ) -> Result<(cookie::CookieJar, Redirect), ErrorResponse> {
-        let params = entity::GetUserParams::ByDisplayId(req.display_id);
-        let user = state.get_user(params).await?;
-        let params = entity::VerifyUserPasswordParams {
+        use crate::error::RejectKind;
+
+        let LoginUserRequest {
+            display_id,
+            password,
+        } = req;
+        let params = entity::AuthenticateUserParams {
+            display_id: display_id.clone(),
+            raw_password: password,
+        };
+        let user = state.authenticate_user(params).await;
+        crate::metrics::record_login(&user);
+        let user = match user {
+            Ok(user) => user,
+            Err(e) => {
+                // looked up whatever the failure, so that an unknown account takes no less time
+                let params = entity::GetUserParams::ByDisplayId(display_id.clone());
+                let subject = state.get_user(params).await.ok().map(|u| u.id);
+                let kind = entity::AuditEventKind::LoginFailure;
+                let detail = format!("display_id={display_id}: {e}");
+                state
+                    .audit(&client, kind, None, subject, Some(detail))
+                    .await;
+                // the reason stays in the audit log; clients cannot tell an unknown user from a
+                // wrong password, while the status of an account is only told to its owner
+                let e = match e {
+                    Failure::Reject(r)
+                        if matches!(r.kind(), RejectKind::NotFound | RejectKind::Unauthorized) =>
+                    {
+                        Failure::unauthorized("Invalid display id or password")
+                    }
+                    e => e,
+                };
+                return Err(e.into());
+            }
+        };
+        let kind = entity::AuditEventKind::LoginSuccess;
+        state.audit(&client, kind, Some(user.id), None, None).await;
+        let roles = state.get_user_roles(user.id).await?;
+        let params = entity::MakeCredentialParams {
             user_id: user.id,
-            raw: req.password,
+            roles,
+            user_agent: client.user_agent.clone(),
+            ip: client.ip,
         };
-        let verification = state.verify_user_password(params).await?;
-        if !verification {
-            let e = Failure::unauthorized("unauthorized");
-            return Err(e.into());
-        }
-        let params = entity::MakeCredentialParams { user_id: user.id };
         let entity::Credential(cookie_value) = state.make_credential(params).await?;
         let prefix = state.path_prefix();
-        let cookie = cookie::Cookie::build((state.cookie_name().to_string(), cookie_value))
-            .path(prefix.to_string())
-            .http_only(true)
-            .build();
-        let cookie_jar = cookie_jar.add(cookie);
+        let cookie_jar = cookie_jar.add(state.session_cookie(&client, cookie_value));
         Ok((cookie_jar, Redirect::to(&format!("{prefix}me.html"))))
     }
 
-    #[expect(clippy::unused_async)]
     async fn logout(
         State(state): State<Self>,
+        client: ClientInfo,
+        principal: Option<Authenticated>,
         cookie_jar: cookie::CookieJar,
     ) -> Result<(cookie::CookieJar, Redirect), ErrorResponse> {
-        let cookie_name = state.cookie_name();
+        let cookie_name = state.session_cookie_name();
         let cookie_value = cookie_jar
-            .get(cookie_name)
+            .get(&cookie_name)
             .ok_or_else(|| Failure::unauthorized("Unauthorized"))?
             .value();
-        let _credential = entity::Credential(cookie_value.to_string());
-        // TODO: self.revoke_credential(credential).await?;
-        let prefix = state.path_prefix();
-        let cookie = cookie::Cookie::build(cookie_name.to_string())
-            .removal()
-            .path(prefix.to_string())
-            .http_only(true)
-            .build();
-        let cookie_jar = cookie_jar.add(cookie);
-        Ok((cookie_jar, Redirect::to(prefix)))
+        let credential = entity::Credential(cookie_value.to_string());
+        match state.revoke_credential(credential).await {
+            Ok(()) => {}
+            // the session is gone already; still clear the cookie
+            Err(Failure::Reject(r)) => tracing::debug!("Reject: {r}"),
+            Err(e) => return Err(e.into()),
+        }
+        let actor = principal.map(|Authenticated(p)| p.user_id);
+        let kind = entity::AuditEventKind::Logout;
+        state.audit(&client, kind, actor, None, None).await;
+        let cookie_jar = cookie_jar.add(state.removal_cookie(&client));
+        Ok((cookie_jar, Redirect::to(state.path_prefix())))
     }
 
-    async fn me(
+    /// Attaches the [`entity::Principal`] of a valid session cookie to the request.
+    async fn authenticate(
         State(state): State<Self>,
         cookie_jar: cookie::CookieJar,
+        mut req: Request,
+        next: Next,
+    ) -> Response {
+        if let Some(cookie) = cookie_jar.get(&state.session_cookie_name()) {
+            let credential = entity::Credential(cookie.value().to_string());
+            let principal = state.check_credential(credential).await;
+            crate::metrics::record_credential_check(&principal);
+            match principal {
+                Ok(principal) => {
+                    req.extensions_mut().insert(principal);
+                }
+                Err(Failure::Reject(r)) => tracing::debug!("Reject: {r}"),
+                Err(e) => return ErrorResponse::from(e).into_response(),
+            }
+        }
+        next.run(req).await
+    }
+
+    async fn me(
+        State(state): State<Self>,
+        Authenticated(principal): Authenticated,
     ) -> Result<Json<entity::User>, ErrorResponse> {
-        let session_cookie = cookie_jar
-            .get(state.cookie_name())
-            .context("Unauthenticated")?
-            .value();
-        let credential = entity::Credential(session_cookie.to_string());
-        let user_id = state.check_credential(credential).await?;
-        // let user_id = app.token_manager.decode(session_cookie)?;
-        let params = entity::GetUserParams::ById(user_id);
+        let params = entity::GetUserParams::ById(principal.user_id);
         let user = state.get_user(params).await?;
         Ok(Json(user))
     }
 
-    fn router() -> axum::Router<Self> {
-        use axum::routing::{get, post};
+    async fn update_me(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        Json(req): Json<UpdateMeRequest>,
+    ) -> Result<Json<entity::User>, ErrorResponse> {
+        let UpdateMeRequest { display_id, name } = req;
+        let params = entity::UpdateUserParams {
+            id: principal.user_id,
+            display_id,
+            name,
+        };
+        let user = state.update_user(params).await?;
+        let kind = entity::AuditEventKind::AccountUpdate;
+        state.audit(&client, kind, Some(user.id), None, None).await;
+        Ok(Json(user))
+    }
+
+    async fn change_password(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        Json(req): Json<ChangePasswordRequest>,
+    ) -> Result<StatusCode, ErrorResponse> {
+        let ChangePasswordRequest {
+            current_password,
+            new_password,
+        } = req;
+        let params = entity::VerifyUserPasswordParams {
+            user_id: principal.user_id,
+            raw: current_password,
+        };
+        if !state.verify_user_password(params).await? {
+            return Err(Failure::unauthorized("Password mismatch").into());
+        }
+        let params = entity::UpdateUserPasswordParams {
+            user_id: principal.user_id,
+            new_raw: new_password,
+        };
+        state.update_user_password(params).await?;
+        let kind = entity::AuditEventKind::PasswordChange;
+        state
+            .audit(&client, kind, Some(principal.user_id), None, None)
+            .await;
+        Ok(StatusCode::NO_CONTENT)
+    }
 
-        axum::Router::new()
+    async fn delete_me(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        cookie_jar: cookie::CookieJar,
+        Json(req): Json<DeleteMeRequest>,
+    ) -> Result<(cookie::CookieJar, StatusCode), ErrorResponse> {
+        let params = entity::DeleteUserParams {
+            user_id: principal.user_id,
+            raw_password: req.password,
+        };
+        state.delete_user(params).await?;
+        let kind = entity::AuditEventKind::AccountDelete;
+        state
+            .audit(&client, kind, Some(principal.user_id), None, None)
+            .await;
+        Ok((
+            cookie_jar.add(state.removal_cookie(&client)),
+            StatusCode::NO_CONTENT,
+        ))
+    }
+
+    async fn get_users(
+        State(state): State<Self>,
+        Query(params): Query<entity::GetUsersParams>,
+    ) -> Result<Json<entity::UserPage>, ErrorResponse> {
+        let page = state.get_users(params).await?;
+        Ok(Json(page))
+    }
+
+    async fn my_sessions(
+        State(state): State<Self>,
+        Authenticated(principal): Authenticated,
+    ) -> Result<Json<Vec<SessionResponse>>, ErrorResponse> {
+        let sessions = state
+            .get_sessions(principal.user_id)
+            .await?
+            .into_iter()
+            .map(|session| SessionResponse {
+                current: session.id == principal.session_id,
+                session,
+            })
+            .collect();
+        Ok(Json(sessions))
+    }
+
+    async fn revoke_my_session(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        cookie_jar: cookie::CookieJar,
+        Path(id): Path<entity::SessionId>,
+    ) -> Result<(cookie::CookieJar, StatusCode), ErrorResponse> {
+        let params = entity::DeleteSessionParams {
+            user_id: principal.user_id,
+            id,
+        };
+        state.revoke_session(params).await?;
+        let kind = entity::AuditEventKind::Logout;
+        let detail = format!("revoke session {}", id.0);
+        state
+            .audit(&client, kind, Some(principal.user_id), None, Some(detail))
+            .await;
+        let cookie_jar = if id == principal.session_id {
+            cookie_jar.add(state.removal_cookie(&client))
+        } else {
+            cookie_jar
+        };
+        Ok((cookie_jar, StatusCode::NO_CONTENT))
+    }
+
+    /// Signs out everywhere, including the current session.
+    async fn revoke_my_sessions(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        cookie_jar: cookie::CookieJar,
+    ) -> Result<(cookie::CookieJar, StatusCode), ErrorResponse> {
+        let revoked = state.revoke_all_sessions(principal.user_id).await?;
+        let kind = entity::AuditEventKind::Logout;
+        let detail = format!("revoke all {revoked} sessions");
+        state
+            .audit(&client, kind, Some(principal.user_id), None, Some(detail))
+            .await;
+        Ok((
+            cookie_jar.add(state.removal_cookie(&client)),
+            StatusCode::NO_CONTENT,
+        ))
+    }
+
+    async fn my_audit_events(
+        State(state): State<Self>,
+        Authenticated(principal): Authenticated,
+        Query(params): Query<entity::SearchAuditEventsParams>,
+    ) -> Result<Json<Vec<entity::AuditEvent>>, ErrorResponse> {
+        let params = entity::SearchAuditEventsParams {
+            user_id: Some(principal.user_id),
+            ..params
+        };
+        let events = state.search_audit_events(params).await?;
+        Ok(Json(events))
+    }
+
+    async fn search_audit_events(
+        State(state): State<Self>,
+        Query(params): Query<entity::SearchAuditEventsParams>,
+    ) -> Result<Json<Vec<entity::AuditEvent>>, ErrorResponse> {
+        let events = state.search_audit_events(params).await?;
+        Ok(Json(events))
+    }
+
+    async fn set_user_status(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        Path(id): Path<entity::UserId>,
+        Json(status): Json<entity::UserStatus>,
+    ) -> Result<Json<entity::User>, ErrorResponse> {
+        let params = entity::SetUserStatusParams { id, status };
+        let user = state.set_user_status(params).await?;
+        let kind = entity::AuditEventKind::AdminAction;
+        let detail = format!("set status {}", status.as_str());
+        state
+            .audit(
+                &client,
+                kind,
+                Some(principal.user_id),
+                Some(id),
+                Some(detail),
+            )
+            .await;
+        Ok(Json(user))
+    }
+
+    async fn get_user_roles(
+        State(state): State<Self>,
+        Path(user_id): Path<entity::UserId>,
+    ) -> Result<Json<Vec<entity::Role>>, ErrorResponse> {
+        let roles = state.get_user_roles(user_id).await?;
+        Ok(Json(roles))
+    }
+
+    async fn grant_role(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        Path((user_id, role)): Path<(entity::UserId, String)>,
+    ) -> Result<StatusCode, ErrorResponse> {
+        let detail = format!("grant role {role}");
+        let params = entity::GrantRoleParams { user_id, role };
+        state.grant_role(params).await?;
+        let kind = entity::AuditEventKind::AdminAction;
+        let actor = Some(principal.user_id);
+        state
+            .audit(&client, kind, actor, Some(user_id), Some(detail))
+            .await;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    async fn revoke_role(
+        State(state): State<Self>,
+        client: ClientInfo,
+        Authenticated(principal): Authenticated,
+        Path((user_id, role)): Path<(entity::UserId, String)>,
+    ) -> Result<StatusCode, ErrorResponse> {
+        let detail = format!("revoke role {role}");
+        let params = entity::RevokeRoleParams { user_id, role };
+        state.revoke_role(params).await?;
+        let kind = entity::AuditEventKind::AdminAction;
+        let actor = Some(principal.user_id);
+        state
+            .audit(&client, kind, actor, Some(user_id), Some(detail))
+            .await;
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    /// Reports 503 while any dependency is down or the server is draining.
+    async fn readyz(State(state): State<Self>) -> impl IntoResponse {
+        let report = state.check_readiness().await;
+        let status = if report.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status, Json(report))
+    }
+
+    fn router(state: &Self) -> axum::Router<Self> {
+        use axum::extract::DefaultBodyLimit;
+        use axum::middleware::from_fn_with_state;
+        use axum::routing::{delete, get, post, put};
+        use tower::limit::GlobalConcurrencyLimitLayer;
+
+        let limits = state.limits();
+        // shared by every route, and inside the timeouts so that waiting for a slot counts;
+        // probes stay out of it, to answer while the API is saturated
+        let concurrency = GlobalConcurrencyLimitLayer::new(limits.concurrency_limit);
+        let hashing = axum::Router::new()
             .route("/register", post(Self::register))
             .route("/login", post(Self::login))
+            .route("/me", delete(Self::delete_me))
+            .route("/me/password", put(Self::change_password))
+            .layer(concurrency.clone())
+            .layer(limits::timeout(limits.hashing_timeout));
+        axum::Router::new()
+            .route("/csrf-token", get(Self::csrf_token))
             .route("/logout", post(Self::logout))
-            .route("/me", get(Self::me))
+            .route("/me", get(Self::me).patch(Self::update_me))
+            .route(
+                "/me/sessions",
+                get(Self::my_sessions).delete(Self::revoke_my_sessions),
+            )
+            .route("/me/sessions/{session_id}", delete(Self::revoke_my_session))
+            .route("/me/audit-events", get(Self::my_audit_events))
+            .route(
+                "/audit-events",
+                get(Self::search_audit_events).route_layer(RequirePermission("audit:read")),
+            )
+            .route(
+                "/users",
+                get(Self::get_users).route_layer(RequirePermission("users:read")),
+            )
+            .route(
+                "/users/{user_id}/status",
+                put(Self::set_user_status).route_layer(RequirePermission("users:write")),
+            )
+            .route(
+                "/users/{user_id}/roles",
+                get(Self::get_user_roles).route_layer(RequirePermission("users:read")),
+            )
+            .route(
+                "/users/{user_id}/roles/{role}",
+                put(Self::grant_role)
+                    .delete(Self::revoke_role)
+                    .route_layer(RequirePermission("roles:write")),
+            )
+            .layer(concurrency)
+            .layer(limits::timeout(limits.request_timeout))
+            .merge(hashing)
+            .layer(DefaultBodyLimit::max(limits.body_limit))
+            .layer(from_fn_with_state(state.clone(), Self::authenticate))
     }
 }
 
 pub fn make<S>(state: Arc<S>) -> axum::Router
 where
-    S: entity::ProvideUserRegistry + entity::ProvideCredentialManager + RouteConfig + 'static,
+    S: StateRequirements,
 {
-    use tower_http::services::ServeDir;
+    use axum::middleware::{from_fn, from_fn_with_state};
+    use tower_http::trace::TraceLayer;
 
     let state = AppState(state);
-    let inner = axum::Router::new()
+    let probes = axum::Router::new()
         .route("/ping", axum::routing::get(|| async { "pong" }))
-        .nest("/api", AppState::<S>::router())
-        .fallback_service(ServeDir::new("./dist"));
+        .route("/healthz", axum::routing::get(|| async { "ok" }))
+        .route("/readyz", axum::routing::get(AppState::readyz));
+    let api = crate::metrics::track(AppState::router(&state))
+        .layer(from_fn_with_state(state.clone(), AppState::verify_csrf));
+    // outside the CSRF check and the limits, which preflights have no business with
+    let api = match state.cors().layer() {
+        Some(cors) => api.layer(cors).layer(from_fn_with_state(
+            state.clone(),
+            AppState::reject_preflight,
+        )),
+        None => api,
+    };
+    let inner = crate::metrics::track(crate::metrics::route(probes))
+        .nest("/api", api)
+        .fallback_service(assets::router(state.assets()))
+        .layer(from_fn_with_state(
+            state.clone(),
+            AppState::security_headers,
+        ));
     let prefix = state.path_prefix();
     let router = if prefix == "/" {
         inner
     } else {
         axum::Router::new().nest(prefix, inner)
     };
-    router.with_state(state)
+    router
+        .layer(TraceLayer::new_for_http().make_span_with(request_id::make_span))
+        .layer(from_fn(request_id::propagate))
+        .with_state(state)
 }
@@ -0,0 +1,215 @@
+#[cfg(feature = "embed-assets")]
+mod embedded;
+
+use std::path::PathBuf;
+
+use axum::extract::Request;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Vite's `build.assetsDir`, whose file names carry a content hash.
+const HASHED_DIR: &str = "/assets/";
+
+/// Served with a 404 for unknown paths, unless falling back to `index.html`.
+const NOT_FOUND_PAGE: &str = "404.html";
+
+/// Where the built client is served from.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct Assets {
+    /// Relative paths resolve against the working directory.
+    pub dir: PathBuf,
+    /// Serve the client built into the binary with the `embed-assets` feature instead of `dir`.
+    pub embedded: bool,
+    /// Answer unknown paths with `index.html` rather than the 404 page, for client-side routing.
+    pub spa_fallback: bool,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("dist"),
+            embedded: cfg!(feature = "embed-assets"),
+            spa_fallback: false,
+        }
+    }
+}
+
+/// Serves the client, preferring `.br` and `.gz` siblings of a file when the client accepts them.
+pub(super) fn router(assets: &Assets) -> axum::Router {
+    use axum::middleware::from_fn;
+    use tower_http::services::{ServeDir, ServeFile};
+
+    #[cfg(feature = "embed-assets")]
+    if assets.embedded {
+        return embedded::router(assets.spa_fallback).layer(from_fn(cache_control));
+    }
+    #[cfg(not(feature = "embed-assets"))]
+    if assets.embedded {
+        tracing::warn!("Built without the embed-assets feature; serving the assets directory");
+    }
+    let dir = &assets.dir;
+    if !dir.is_dir() {
+        tracing::warn!(dir = %dir.display(), "The assets directory does not exist");
+    }
+    let serve_dir = ServeDir::new(dir).precompressed_br().precompressed_gzip();
+    let router = if assets.spa_fallback {
+        let index = ServeFile::new(dir.join("index.html"))
+            .precompressed_br()
+            .precompressed_gzip();
+        axum::Router::new().fallback_service(serve_dir.fallback(index))
+    } else {
+        let not_found = ServeFile::new(dir.join(NOT_FOUND_PAGE))
+            .precompressed_br()
+            .precompressed_gzip();
+        axum::Router::new().fallback_service(serve_dir.not_found_service(not_found))
+    };
+    router.layer(from_fn(cache_control))
+}
+
+/// Lets browsers keep hashed assets for good, and revalidate everything else.
+async fn cache_control(req: Request, next: Next) -> Response {
+    let hashed = req.uri().path().starts_with(HASHED_DIR);
+    let mut res = next.run(req).await;
+    let status = res.status();
+    // HTML under the hashed directory is the SPA fallback for an asset that does not exist
+    let html = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/html"));
+    let immutable = hashed && !html && (status.is_success() || status == StatusCode::NOT_MODIFIED);
+    let value = if immutable {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    res.headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static(value));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    /// A client build in a fresh directory, removed on drop.
+    struct Dist(PathBuf);
+
+    impl Dist {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("dist-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(dir.join("assets")).unwrap();
+            let files = [
+                ("index.html", "index"),
+                ("404.html", "not found"),
+                ("assets/app-3f2a.js", "app"),
+                ("assets/app-3f2a.js.gz", "gzipped app"),
+            ];
+            for (path, contents) in files {
+                std::fs::write(dir.join(path), contents).unwrap();
+            }
+            Self(dir)
+        }
+
+        fn router(&self, spa_fallback: bool) -> axum::Router {
+            router(&Assets {
+                dir: self.0.clone(),
+                embedded: false,
+                spa_fallback,
+            })
+        }
+    }
+
+    impl Drop for Dist {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    async fn get(
+        router: &axum::Router,
+        path: &str,
+        encoding: &str,
+    ) -> (StatusCode, String, String) {
+        let req = Request::get(path)
+            .header(header::ACCEPT_ENCODING, encoding)
+            .body(Body::empty())
+            .unwrap();
+        let res = router.clone().oneshot(req).await.unwrap();
+        let cache_control = res.headers()[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            cache_control,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn assets_are_served_with_caching_and_a_404_page() {
+        let dist = Dist::new();
+        let immutable = "public, max-age=31536000, immutable";
+        let cases = [
+            (
+                false,
+                "/assets/app-3f2a.js",
+                "identity",
+                StatusCode::OK,
+                immutable,
+                "app",
+            ),
+            (
+                false,
+                "/assets/app-3f2a.js",
+                "gzip",
+                StatusCode::OK,
+                immutable,
+                "gzipped app",
+            ),
+            (false, "/", "identity", StatusCode::OK, "no-cache", "index"),
+            (
+                false,
+                "/settings",
+                "identity",
+                StatusCode::NOT_FOUND,
+                "no-cache",
+                "not found",
+            ),
+            (
+                true,
+                "/settings",
+                "identity",
+                StatusCode::OK,
+                "no-cache",
+                "index",
+            ),
+            // a missing hashed asset must not be cached as the fallback page
+            (
+                true,
+                "/assets/gone-1b2c.js",
+                "identity",
+                StatusCode::OK,
+                "no-cache",
+                "index",
+            ),
+        ];
+        for (spa_fallback, path, encoding, status, cache_control, body) in cases {
+            let res = get(&dist.router(spa_fallback), path, encoding).await;
+            assert_eq!(
+                res,
+                (status, cache_control.to_string(), body.to_string()),
+                "{path}"
+            );
+        }
+    }
+}
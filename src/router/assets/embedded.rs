@@ -0,0 +1,117 @@
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+
+/// A file of the built client, listed by `build.rs`.
+struct File {
+    /// Relative to the build directory, with `/` separators.
+    path: &'static str,
+    bytes: &'static [u8],
+    /// Weak, as the precompressed variants share it.
+    etag: &'static str,
+}
+
+// `FILES`, sorted by path
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// Content codings tried in order of preference, with the extension of their variants.
+const ENCODINGS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
+fn find(path: &str) -> Option<&'static File> {
+    let i = FILES.binary_search_by(|f| f.path.cmp(path)).ok()?;
+    Some(&FILES[i])
+}
+
+pub(super) fn router(spa_fallback: bool) -> axum::Router {
+    if FILES.is_empty() {
+        tracing::warn!("No assets were embedded; build the client before the server");
+    }
+    axum::Router::new().fallback(move |req: Request| async move { serve(&req, spa_fallback) })
+}
+
+fn serve(req: &Request, spa_fallback: bool) -> Response {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return (
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, "GET,HEAD")],
+        )
+            .into_response();
+    }
+    let path = req.uri().path().trim_start_matches('/');
+    let index;
+    let path = if path.is_empty() || path.ends_with('/') {
+        index = format!("{path}index.html");
+        &index
+    } else {
+        path
+    };
+    let found = find(path).map(|f| (StatusCode::OK, f)).or_else(|| {
+        if spa_fallback {
+            find("index.html").map(|f| (StatusCode::OK, f))
+        } else {
+            find(super::NOT_FOUND_PAGE).map(|f| (StatusCode::NOT_FOUND, f))
+        }
+    });
+    let Some((status, file)) = found else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, HeaderValue::from_static(file.etag));
+    if status == StatusCode::OK && if_none_match(req.headers(), file.etag) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    let mime = mime_guess::from_path(file.path).first_raw();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(mime.unwrap_or("application/octet-stream")),
+    );
+    let mut body = file.bytes;
+    let mut varies = false;
+    for (encoding, extension) in ENCODINGS {
+        let Some(variant) = find(&format!("{}.{extension}", file.path)) else {
+            continue;
+        };
+        varies = true;
+        if !headers.contains_key(header::CONTENT_ENCODING) && accepts(req.headers(), encoding) {
+            body = variant.bytes;
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+    }
+    if varies {
+        headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    (status, headers, Body::from(body)).into_response()
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// Whether `Accept-Encoding` lists `encoding` without ruling it out with `q=0`.
+fn accepts(headers: &HeaderMap, encoding: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|item| {
+            let mut params = item.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            name.eq_ignore_ascii_case(encoding)
+                && !params.any(|p| {
+                    let q = p
+                        .trim()
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok());
+                    q.is_some_and(|q| q <= 0.0)
+                })
+        })
+}
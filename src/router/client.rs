@@ -0,0 +1,442 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::header::{FORWARDED, USER_AGENT};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderName};
+
+use super::{AppState, StateRequirements};
+use crate::proxy::TrustedProxies;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Marks requests on a connection whose TLS is terminated in-process, so they count as HTTPS.
+///
+/// Added as a request extension by the server for its TLS listener.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TlsConnection;
+
+/// Request metadata recorded alongside audit events.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    /// The client address, as reported by trusted proxies or else the peer address.
+    pub ip: Option<IpAddr>,
+    /// `http` or `https`, when reported by a trusted proxy or known from the connection.
+    pub proto: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl ClientInfo {
+    /// Whether the client connected over HTTPS, here or to a trusted proxy.
+    #[must_use]
+    pub fn is_https(&self) -> bool {
+        self.proto.as_deref() == Some("https")
+    }
+}
+
+impl<S> FromRequestParts<AppState<S>> for ClientInfo
+where
+    S: StateRequirements,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState<S>,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = Hop {
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+            proto: parts
+                .extensions
+                .get::<TlsConnection>()
+                .map(|TlsConnection| "https".to_string()),
+        };
+        let Hop { ip, proto } = resolve(peer, &parts.headers, state.trusted_proxies());
+        let header = |name| {
+            let value = parts.headers.get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        Ok(Self {
+            ip,
+            proto,
+            user_agent: header(USER_AGENT.as_str()),
+            request_id: header("x-request-id"),
+        })
+    }
+}
+
+/// One proxy hop: the address a proxy saw, and the scheme it was reached with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Hop {
+    /// `None` for obfuscated or `unknown` nodes.
+    ip: Option<IpAddr>,
+    proto: Option<String>,
+}
+
+/// Walks the forwarding headers back from the peer while the hops are trusted.
+///
+/// A peer without an address, connected over a Unix socket, is trusted only when Unix socket
+/// peers are.
+fn resolve(peer: Hop, headers: &HeaderMap, trusted: &TrustedProxies) -> Hop {
+    let peer_trusted = match peer.ip {
+        Some(ip) => trusted.contains(ip),
+        None => trusted.trusts_unix_socket(),
+    };
+    let mut client = peer;
+    if !peer_trusted {
+        return client;
+    }
+    let hops = forwarded(headers).unwrap_or_else(|| x_forwarded(headers));
+    for hop in hops.into_iter().rev() {
+        let Some(ip) = hop.ip else {
+            break;
+        };
+        client = hop;
+        if !trusted.contains(ip) {
+            break;
+        }
+    }
+    client
+}
+
+fn values(headers: &HeaderMap, name: HeaderName) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(name)
+        .into_iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+}
+
+fn parse_proto(value: &str) -> Option<String> {
+    let proto = value.trim_matches('"').to_ascii_lowercase();
+    matches!(proto.as_str(), "http" | "https").then_some(proto)
+}
+
+/// Parses a node like `192.0.2.1`, `192.0.2.1:80`, `[2001:db8::1]:80` or `2001:db8::1`.
+fn parse_node(value: &str) -> Option<IpAddr> {
+    let value = value.trim_matches('"');
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    value
+        .parse()
+        .ok()
+        .or_else(|| value.parse::<SocketAddr>().ok().map(|a| a.ip()))
+}
+
+/// The hops of the RFC 7239 `Forwarded` header, client first, if present.
+fn forwarded(headers: &HeaderMap) -> Option<Vec<Hop>> {
+    headers.get(FORWARDED)?;
+    let hops = values(headers, FORWARDED).map(|element| {
+        let mut hop = Hop::default();
+        for pair in element.split(';') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            match key.trim().to_ascii_lowercase().as_str() {
+                "for" => hop.ip = parse_node(value.trim()),
+                "proto" => hop.proto = parse_proto(value.trim()),
+                _ => {}
+            }
+        }
+        hop
+    });
+    Some(hops.collect())
+}
+
+/// The hops of `X-Forwarded-For`, with schemes from `X-Forwarded-Proto` when they line up.
+fn x_forwarded(headers: &HeaderMap) -> Vec<Hop> {
+    let ips: Vec<_> = values(headers, X_FORWARDED_FOR).map(parse_node).collect();
+    let protos: Vec<_> = values(headers, X_FORWARDED_PROTO)
+        .map(parse_proto)
+        .collect();
+    let proto = |i: usize| match protos.len() {
+        1 => protos[0].clone(),
+        n if n == ips.len() => protos[i].clone(),
+        _ => None,
+    };
+    ips.iter()
+        .enumerate()
+        .map(|(i, ip)| Hop {
+            ip: *ip,
+            proto: proto(i),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn trusted() -> TrustedProxies {
+        TrustedProxies::new(vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "2001:db8:ffff::/48".parse().unwrap(),
+        ])
+    }
+
+    /// A name, the peer, the request headers, and the client address and scheme expected.
+    type Case = (
+        &'static str,
+        Option<&'static str>,
+        &'static [(&'static str, &'static str)],
+        Option<&'static str>,
+        Option<&'static str>,
+    );
+
+    const RESOLVE_CASES: &[Case] = &[
+        ("no headers", Some("10.0.0.1"), &[], Some("10.0.0.1"), None),
+        (
+            "untrusted peer spoofing X-Forwarded-For",
+            Some("198.51.100.9"),
+            &[
+                ("x-forwarded-for", "203.0.113.7"),
+                ("x-forwarded-proto", "https"),
+            ],
+            Some("198.51.100.9"),
+            None,
+        ),
+        (
+            "untrusted peer spoofing Forwarded",
+            Some("198.51.100.9"),
+            &[("forwarded", "for=203.0.113.7;proto=https")],
+            Some("198.51.100.9"),
+            None,
+        ),
+        (
+            "trusted peer",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "203.0.113.7")],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "hop spoofed by the client",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "10.0.0.5, 203.0.113.7")],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "chain of trusted proxies",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "203.0.113.7, 10.0.0.3, 10.0.0.2")],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "only trusted hops",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "10.0.0.3, 10.0.0.2")],
+            Some("10.0.0.3"),
+            None,
+        ),
+        (
+            "unknown hop",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "203.0.113.7, unknown, 10.0.0.2")],
+            Some("10.0.0.2"),
+            None,
+        ),
+        (
+            "X-Forwarded-For across header lines",
+            Some("10.0.0.1"),
+            &[
+                ("x-forwarded-for", "198.51.100.1"),
+                ("x-forwarded-for", "203.0.113.7, 10.0.0.2"),
+            ],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "one X-Forwarded-Proto for every hop",
+            Some("10.0.0.1"),
+            &[
+                ("x-forwarded-for", "203.0.113.7, 10.0.0.2"),
+                ("x-forwarded-proto", "https"),
+            ],
+            Some("203.0.113.7"),
+            Some("https"),
+        ),
+        (
+            "X-Forwarded-Proto per hop",
+            Some("10.0.0.1"),
+            &[
+                ("x-forwarded-for", "203.0.113.7, 10.0.0.2"),
+                ("x-forwarded-proto", "https, http"),
+            ],
+            Some("203.0.113.7"),
+            Some("https"),
+        ),
+        (
+            "X-Forwarded-Proto not lining up",
+            Some("10.0.0.1"),
+            &[
+                ("x-forwarded-for", "203.0.113.7, 10.0.0.3, 10.0.0.2"),
+                ("x-forwarded-proto", "https, http"),
+            ],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "IPv6 with brackets and a port",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "[2001:db8::7]:4711")],
+            Some("2001:db8::7"),
+            None,
+        ),
+        (
+            "bare IPv6",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "2001:db8::7")],
+            Some("2001:db8::7"),
+            None,
+        ),
+        (
+            "IPv4 with a port",
+            Some("10.0.0.1"),
+            &[("x-forwarded-for", "203.0.113.7:80")],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "trusted IPv6 peer and hop",
+            Some("2001:db8:ffff::1"),
+            &[("x-forwarded-for", "2001:db8::7, [2001:db8:ffff::2]:443")],
+            Some("2001:db8::7"),
+            None,
+        ),
+        (
+            "Forwarded",
+            Some("10.0.0.1"),
+            &[("forwarded", "for=203.0.113.7;proto=https")],
+            Some("203.0.113.7"),
+            Some("https"),
+        ),
+        (
+            "Forwarded with several elements",
+            Some("10.0.0.1"),
+            &[(
+                "forwarded",
+                "for=198.51.100.1;proto=http, for=203.0.113.7;proto=https, for=10.0.0.2",
+            )],
+            Some("203.0.113.7"),
+            Some("https"),
+        ),
+        (
+            "Forwarded across header lines",
+            Some("10.0.0.1"),
+            &[
+                ("forwarded", "for=198.51.100.1"),
+                ("forwarded", "for=203.0.113.7;proto=HTTPS"),
+            ],
+            Some("203.0.113.7"),
+            Some("https"),
+        ),
+        (
+            "Forwarded with quoted IPv6",
+            Some("10.0.0.1"),
+            &[("forwarded", "For=\"[2001:db8::7]:4711\";Proto=https")],
+            Some("2001:db8::7"),
+            Some("https"),
+        ),
+        (
+            "Forwarded with another scheme",
+            Some("10.0.0.1"),
+            &[("forwarded", "for=203.0.113.7;proto=ftp")],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "Forwarded with an obfuscated node",
+            Some("10.0.0.1"),
+            &[("forwarded", "for=203.0.113.7, for=_hidden, for=10.0.0.2")],
+            Some("10.0.0.2"),
+            None,
+        ),
+        (
+            "Forwarded takes precedence",
+            Some("10.0.0.1"),
+            &[
+                ("forwarded", "for=203.0.113.7"),
+                ("x-forwarded-for", "198.51.100.1"),
+            ],
+            Some("203.0.113.7"),
+            None,
+        ),
+        (
+            "untrusted Unix socket peer",
+            None,
+            &[("x-forwarded-for", "203.0.113.7")],
+            None,
+            None,
+        ),
+    ];
+
+    #[test]
+    fn resolve_cases() {
+        for (name, peer, pairs, ip, proto) in RESOLVE_CASES {
+            let mut headers = HeaderMap::new();
+            for (k, v) in *pairs {
+                headers.append(HeaderName::from_static(k), HeaderValue::from_static(v));
+            }
+            let peer = Hop {
+                ip: peer.map(|p| p.parse().unwrap()),
+                proto: None,
+            };
+            let expected = Hop {
+                ip: ip.map(|ip| ip.parse().unwrap()),
+                proto: proto.map(ToString::to_string),
+            };
+            assert_eq!(resolve(peer, &headers, &trusted()), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn unix_socket_peers_are_trusted_when_configured() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("203.0.113.7"));
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static("https"));
+        let expected = Hop {
+            ip: Some("203.0.113.7".parse().unwrap()),
+            proto: Some("https".to_string()),
+        };
+        let unix_only = TrustedProxies::default().trust_unix_socket(true);
+        assert_eq!(resolve(Hop::default(), &headers, &unix_only), expected);
+        assert_eq!(
+            resolve(Hop::default(), &headers, &trusted().trust_unix_socket(true)),
+            expected
+        );
+        // an address-bound peer is still matched against the ranges
+        let peer = Hop {
+            ip: Some("10.0.0.1".parse().unwrap()),
+            proto: None,
+        };
+        assert_eq!(resolve(peer.clone(), &headers, &unix_only), peer);
+    }
+
+    #[test]
+    fn tls_peers_are_https_unless_a_trusted_proxy_reports_otherwise() {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("203.0.113.7"));
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static("http"));
+        let tls_peer = |ip: &str| Hop {
+            ip: Some(ip.parse().unwrap()),
+            proto: Some("https".to_string()),
+        };
+        let untrusted = tls_peer("198.51.100.9");
+        assert_eq!(resolve(untrusted.clone(), &headers, &trusted()), untrusted);
+        // the proxy saw the client, so its scheme is the one that counts
+        let hop = resolve(tls_peer("10.0.0.1"), &headers, &trusted());
+        assert_eq!(hop.proto.as_deref(), Some("http"));
+    }
+}
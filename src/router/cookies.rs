@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use anyhow::ensure;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+
+/// Attributes applied to every cookie the API sets.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct CookiePolicy {
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub domain: Option<String>,
+    /// Unset makes session cookies, dropped when the browser closes.
+    pub max_age: Option<Duration>,
+    /// Prefixes names with `__Host-`, pinning cookies to the exact host and path `/`.
+    pub host_prefix: bool,
+}
+
+impl Default for CookiePolicy {
+    fn default() -> Self {
+        Self {
+            secure: true,
+            same_site: SameSite::Lax,
+            domain: None,
+            max_age: None,
+            host_prefix: false,
+        }
+    }
+}
+
+impl CookiePolicy {
+    /// Checks the combinations browsers would silently refuse.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.secure || self.same_site != SameSite::None,
+            "SameSite=None cookies must be secure"
+        );
+        if self.host_prefix {
+            ensure!(self.secure, "__Host- cookies must be secure");
+            ensure!(
+                self.domain.is_none(),
+                "__Host- cookies must not set a domain"
+            );
+        }
+        Ok(())
+    }
+
+    pub(super) fn name(&self, base: &str) -> String {
+        if self.host_prefix {
+            format!("__Host-{base}")
+        } else {
+            base.to_string()
+        }
+    }
+
+    fn apply(&self, cookie: Cookie<'static>, path_prefix: &str) -> Cookie<'static> {
+        let path = if self.host_prefix { "/" } else { path_prefix };
+        let mut builder = Cookie::build(cookie)
+            .path(path.to_string())
+            .secure(self.secure)
+            .same_site(self.same_site);
+        if let Some(domain) = &self.domain {
+            builder = builder.domain(domain.clone());
+        }
+        builder.build()
+    }
+
+    /// Builds the cookie `name=value` with this policy.
+    pub(super) fn build(&self, name: String, value: String, path_prefix: &str) -> Cookie<'static> {
+        let mut cookie = self.apply(Cookie::new(name, value), path_prefix);
+        cookie.set_max_age(self.max_age.and_then(|d| d.try_into().ok()));
+        cookie
+    }
+
+    /// Builds a cookie clearing `name`, matching the attributes it was set with.
+    pub(super) fn removal(&self, name: String, path_prefix: &str) -> Cookie<'static> {
+        let mut cookie = self.apply(Cookie::from(name), path_prefix);
+        cookie.make_removal();
+        cookie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_refuses_what_browsers_drop() {
+        let policy = |secure, same_site, domain: Option<&str>, host_prefix| CookiePolicy {
+            secure,
+            same_site,
+            domain: domain.map(ToString::to_string),
+            max_age: None,
+            host_prefix,
+        };
+        let cases = [
+            (policy(true, SameSite::None, None, false), true),
+            (policy(false, SameSite::None, None, false), false),
+            (policy(false, SameSite::Strict, None, false), true),
+            (policy(true, SameSite::Lax, None, true), true),
+            (policy(false, SameSite::Lax, None, true), false),
+            (
+                policy(true, SameSite::Lax, Some("example.com"), true),
+                false,
+            ),
+            (
+                policy(true, SameSite::Lax, Some("example.com"), false),
+                true,
+            ),
+        ];
+        for (policy, valid) in cases {
+            assert_eq!(policy.validate().is_ok(), valid, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn cookies_carry_the_policy() {
+        let policy = CookiePolicy {
+            same_site: SameSite::Strict,
+            domain: Some("example.com".to_string()),
+            max_age: Some(Duration::from_hours(24)),
+            ..CookiePolicy::default()
+        };
+        let cookie = policy.build(policy.name("session"), "v".into(), "/app/");
+        assert_eq!(cookie.name(), "session");
+        assert_eq!(cookie.path(), Some("/app/"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert!(cookie.max_age().is_some_and(|d| d.whole_hours() == 24));
+
+        let removal = policy.removal(policy.name("session"), "/app/");
+        assert_eq!(removal.value(), "");
+        assert_eq!(removal.path(), cookie.path());
+        assert_eq!(removal.domain(), cookie.domain());
+        assert!(removal.max_age().is_some_and(|d| d.whole_seconds() == 0));
+    }
+
+    #[test]
+    fn host_prefix_pins_the_name_and_path() {
+        let policy = CookiePolicy {
+            host_prefix: true,
+            ..CookiePolicy::default()
+        };
+        let cookie = policy.build(policy.name("session"), "v".into(), "/app/");
+        assert_eq!(cookie.name(), "__Host-session");
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.domain(), None);
+        // without a max age, the cookie lasts the browser session
+        assert_eq!(cookie.max_age(), None);
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::ensure;
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, Method, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::{AppState, StateRequirements};
+
+/// An origin like `https://app.example.com`, `https://*.example.com` for any subdomain, or `*`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OriginPattern {
+    Any,
+    Exact(String),
+    /// The parts around the `*`, which stands for one or more subdomain labels.
+    Subdomains {
+        prefix: String,
+        suffix: String,
+    },
+}
+
+impl OriginPattern {
+    /// Matches the serialized `Origin` header, which browsers send lowercase and without a
+    /// default port.
+    #[must_use]
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => origin == exact,
+            Self::Subdomains { prefix, suffix } => origin
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(|labels| {
+                    !labels.is_empty()
+                        && labels.split('.').all(|label| {
+                            !label.is_empty()
+                                && label
+                                    .bytes()
+                                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+                        })
+                }),
+        }
+    }
+}
+
+impl FromStr for OriginPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "*" {
+            return Ok(Self::Any);
+        }
+        let origin = s.trim_end_matches('/').to_ascii_lowercase();
+        let Some((scheme, authority)) = origin.split_once("://") else {
+            anyhow::bail!("expected an origin like https://example.com");
+        };
+        ensure!(
+            !scheme.is_empty() && !authority.is_empty() && !authority.contains(['/', '?', '#']),
+            "expected an origin like https://example.com, without a path"
+        );
+        if let Some(suffix) = authority.strip_prefix('*') {
+            ensure!(
+                suffix.starts_with('.') && !suffix.contains('*'),
+                "a wildcard only stands for subdomains, as in https://*.example.com"
+            );
+            return Ok(Self::Subdomains {
+                prefix: format!("{scheme}://"),
+                suffix: suffix.to_string(),
+            });
+        }
+        ensure!(!authority.contains('*'), "a wildcard must lead the host");
+        Ok(Self::Exact(origin))
+    }
+}
+
+impl fmt::Display for OriginPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => f.write_str("*"),
+            Self::Exact(origin) => f.write_str(origin),
+            Self::Subdomains { prefix, suffix } => write!(f, "{prefix}*{suffix}"),
+        }
+    }
+}
+
+/// Which other origins may call the API from a browser.
+///
+/// Disallowed origins get no CORS headers, so browsers keep the response from them, and their
+/// preflights are answered without reaching the API.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    /// None disables CORS, leaving the API to same-origin pages.
+    pub origins: Vec<OriginPattern>,
+    /// Lets allowed origins send the session cookie; they then also pass the CSRF origin check.
+    pub credentials: bool,
+    pub methods: Vec<Method>,
+    pub headers: Vec<HeaderName>,
+    /// How long browsers may cache a preflight response.
+    pub max_age: Duration,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            origins: Vec::new(),
+            credentials: false,
+            methods: vec![
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ],
+            headers: vec![
+                header::CONTENT_TYPE,
+                HeaderName::from_static("x-csrf-token"),
+                HeaderName::from_static("x-request-id"),
+            ],
+            max_age: Duration::from_mins(10),
+        }
+    }
+}
+
+impl CorsPolicy {
+    /// Refuses to share credentials with every origin.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.credentials || !self.origins.contains(&OriginPattern::Any),
+            "credentials cannot be allowed for any origin"
+        );
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn allows(&self, origin: &str) -> bool {
+        self.origins.iter().any(|pattern| pattern.matches(origin))
+    }
+
+    /// Whether a state-changing request from `origin` may carry the session cookie.
+    pub(super) fn allows_credentials(&self, origin: &str) -> bool {
+        self.credentials && self.validate().is_ok() && self.allows(origin)
+    }
+
+    /// Whether `origin` may read responses, taking credentials into account.
+    fn allows_origin(&self, origin: &str) -> bool {
+        if self.credentials {
+            self.allows_credentials(origin)
+        } else {
+            self.allows(origin)
+        }
+    }
+
+    pub(super) fn layer(&self) -> Option<CorsLayer> {
+        if self.origins.is_empty() {
+            return None;
+        }
+        let allow_origin = if !self.credentials && self.origins.contains(&OriginPattern::Any) {
+            AllowOrigin::any()
+        } else {
+            let policy = self.clone();
+            AllowOrigin::predicate(move |origin, _| {
+                policy.allows_origin(origin.to_str().unwrap_or_default())
+            })
+        };
+        let layer = CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(self.methods.clone())
+            .allow_headers(self.headers.clone())
+            .allow_credentials(self.credentials)
+            .expose_headers([HeaderName::from_static("x-request-id")])
+            .max_age(self.max_age);
+        Some(layer)
+    }
+}
+
+impl<S> AppState<S>
+where
+    S: StateRequirements,
+{
+    /// Refuses preflights from disallowed origins, which the CORS layer would otherwise answer
+    /// with the methods and headers it allows.
+    pub(super) async fn reject_preflight(
+        State(state): State<Self>,
+        req: Request,
+        next: Next,
+    ) -> Response {
+        let headers = req.headers();
+        let disallowed = req.method() == Method::OPTIONS
+            && headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+            && headers
+                .get(header::ORIGIN)
+                .is_some_and(|o| !state.cors().allows_origin(o.to_str().unwrap_or_default()));
+        if disallowed {
+            let vary = [(header::VARY, HeaderValue::from_static("origin"))];
+            return (StatusCode::FORBIDDEN, vary).into_response();
+        }
+        next.run(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_pattern_matches() {
+        let cases = [
+            ("*", "https://anything.example", true),
+            ("https://app.example.com", "https://app.example.com", true),
+            ("https://app.example.com/", "https://app.example.com", true),
+            ("HTTPS://App.Example.com", "https://app.example.com", true),
+            ("https://app.example.com", "http://app.example.com", false),
+            (
+                "https://app.example.com",
+                "https://app.example.com:8443",
+                false,
+            ),
+            (
+                "https://app.example.com",
+                "https://evil.app.example.com",
+                false,
+            ),
+            ("https://*.example.com", "https://app.example.com", true),
+            ("https://*.example.com", "https://a.b.example.com", true),
+            ("https://*.example.com", "https://app-1.example.com", true),
+            ("https://*.example.com", "https://example.com", false),
+            ("https://*.example.com", "https://.example.com", false),
+            ("https://*.example.com", "https://a..example.com", false),
+            ("https://*.example.com", "https://evil-example.com", false),
+            (
+                "https://*.example.com",
+                "https://example.com.evil.com",
+                false,
+            ),
+            (
+                "https://*.example.com",
+                "https://app.example.com.evil",
+                false,
+            ),
+            ("https://*.example.com", "http://app.example.com", false),
+            (
+                "https://*.example.com",
+                "https://evil.com/.example.com",
+                false,
+            ),
+            (
+                "https://*.example.com",
+                "https://evil.com?.example.com",
+                false,
+            ),
+            (
+                "https://*.example.com",
+                "https://app.example.com:8443",
+                false,
+            ),
+            (
+                "https://*.example.com:8443",
+                "https://app.example.com:8443",
+                true,
+            ),
+            ("https://*.example.com", "null", false),
+        ];
+        for (pattern, origin, expected) in cases {
+            let parsed: OriginPattern = pattern.parse().unwrap();
+            assert_eq!(parsed.matches(origin), expected, "{pattern} {origin}");
+        }
+    }
+
+    #[test]
+    fn origin_pattern_rejects_malformed() {
+        for pattern in [
+            "example.com",
+            "https://",
+            "://example.com",
+            "https://example.com/app",
+            "https://example.com?x",
+            "https://*example.com",
+            "https://*.*.example.com",
+            "https://app.*.example.com",
+            "https://app.example.*",
+        ] {
+            assert!(pattern.parse::<OriginPattern>().is_err(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn origin_pattern_displays_as_parsed() {
+        for pattern in ["*", "https://app.example.com", "https://*.example.com"] {
+            let parsed: OriginPattern = pattern.parse().unwrap();
+            assert_eq!(parsed.to_string(), pattern);
+        }
+    }
+
+    #[test]
+    fn credentials_are_never_shared_with_any_origin() {
+        let cors = CorsPolicy {
+            origins: vec![OriginPattern::Any],
+            credentials: true,
+            ..CorsPolicy::default()
+        };
+        assert!(cors.validate().is_err());
+        assert!(!cors.allows_credentials("https://app.example.com"));
+    }
+}
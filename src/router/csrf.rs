@@ -0,0 +1,279 @@
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_TYPE, HOST, ORIGIN, REFERER};
+use axum::http::{HeaderMap, HeaderName, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum_extra::extract::cookie;
+use serde::Serialize;
+
+use super::{AppState, ClientInfo, CorsPolicy, ErrorResponse, StateRequirements};
+use crate::Failure;
+
+/// Header carrying the token for script requests.
+const HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+/// Form field carrying the token for HTML form posts.
+const FIELD: &str = "csrf_token";
+/// Largest form body buffered to look for [`FIELD`].
+const MAX_FORM_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct CsrfTokenResponse {
+    token: String,
+}
+
+fn generate_token() -> String {
+    let (a, b) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    format!("{}{}", a.simple(), b.simple())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Splits `url` into its `scheme://authority` origin and authority.
+fn origin_of(url: &str) -> Option<(&str, &str)> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let origin_len = url.len() - rest.len() + authority.len();
+    Some((&url[..origin_len], authority))
+}
+
+/// Accepts requests whose `Origin`, or else `Referer`, is this host, a trusted origin, or one
+/// allowed to send credentials through CORS.
+fn check_origin(
+    headers: &HeaderMap,
+    trusted_origins: &[String],
+    cors: &CorsPolicy,
+) -> Result<(), Failure> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let Some(source) = header(ORIGIN).or_else(|| header(REFERER)) else {
+        // non-browser clients send neither; the token check still applies
+        return Ok(());
+    };
+    let (origin, authority) =
+        origin_of(source).ok_or_else(|| Failure::forbidden("Cross-site request rejected"))?;
+    if header(HOST) == Some(authority)
+        || trusted_origins.iter().any(|o| o == origin)
+        || cors.allows_credentials(origin)
+    {
+        Ok(())
+    } else {
+        Err(Failure::forbidden("Cross-site request rejected"))
+    }
+}
+
+impl<S> AppState<S>
+where
+    S: StateRequirements,
+{
+    pub(super) fn csrf_cookie_name(&self) -> String {
+        let base = format!("{}-csrf", self.cookie_name());
+        self.cookie_policy().name(&base)
+    }
+
+    /// Returns the CSRF token, setting the cookie it is checked against if missing.
+    #[expect(clippy::unused_async)]
+    pub(super) async fn csrf_token(
+        State(state): State<Self>,
+        client: ClientInfo,
+        cookie_jar: cookie::CookieJar,
+    ) -> (cookie::CookieJar, Json<CsrfTokenResponse>) {
+        let name = state.csrf_cookie_name();
+        if let Some(cookie) = cookie_jar.get(&name) {
+            let token = cookie.value().to_string();
+            return (cookie_jar, Json(CsrfTokenResponse { token }));
+        }
+        let token = generate_token();
+        // readable by scripts on purpose, as cross-site pages cannot read it; it shares the
+        // session cookie's SameSite so that credentialed CORS origins are sent both
+        let cookie =
+            state
+                .cookie_policy_for(&client)
+                .build(name, token.clone(), state.path_prefix());
+        (cookie_jar.add(cookie), Json(CsrfTokenResponse { token }))
+    }
+
+    /// Rejects state-changing requests from other origins or without a matching CSRF token.
+    pub(super) async fn verify_csrf(
+        State(state): State<Self>,
+        cookie_jar: cookie::CookieJar,
+        req: Request,
+        next: Next,
+    ) -> Response {
+        if matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        ) {
+            return next.run(req).await;
+        }
+        match state.check_csrf(&cookie_jar, req).await {
+            Ok(req) => next.run(req).await,
+            Err(e) => ErrorResponse::from(e).into_response(),
+        }
+    }
+
+    async fn check_csrf(
+        &self,
+        cookie_jar: &cookie::CookieJar,
+        req: Request,
+    ) -> Result<Request, Failure> {
+        check_origin(req.headers(), self.trusted_origins(), self.cors())?;
+        let expected = cookie_jar
+            .get(&self.csrf_cookie_name())
+            .map(|c| c.value().to_string())
+            .ok_or_else(|| Failure::forbidden("Missing CSRF cookie"))?;
+        if let Some(token) = req.headers().get(HEADER) {
+            if constant_time_eq(token.as_bytes(), expected.as_bytes()) {
+                return Ok(req);
+            }
+            return Err(Failure::forbidden("CSRF token mismatch"));
+        }
+        let is_form = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+        if !is_form {
+            return Err(Failure::forbidden("Missing CSRF token"));
+        }
+        // buffer the form to read the token, then hand the body on unchanged
+        let (parts, body) = req.into_parts();
+        let bytes = axum::body::to_bytes(body, MAX_FORM_BYTES)
+            .await
+            .map_err(|_| Failure::bad_request("Form body too large"))?;
+        let matches = form_urlencoded::parse(&bytes)
+            .find(|(k, _)| k == FIELD)
+            .is_some_and(|(_, v)| constant_time_eq(v.as_bytes(), expected.as_bytes()));
+        if !matches {
+            return Err(Failure::forbidden("CSRF token mismatch"));
+        }
+        Ok(Request::from_parts(parts, Body::from(bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::OriginPattern;
+
+    fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    /// A name, the request headers, the trusted origins and whether the request passes.
+    type Case<'a> = (&'a str, &'a [(HeaderName, &'a str)], &'a [&'a str], bool);
+
+    fn check(pairs: &[(HeaderName, &str)], trusted: &[&str], cors: &CorsPolicy) -> bool {
+        let trusted: Vec<_> = trusted.iter().map(ToString::to_string).collect();
+        check_origin(&headers(pairs), &trusted, cors).is_ok()
+    }
+
+    #[test]
+    fn check_origin_cases() {
+        let host = (HOST, "app.example.com");
+        let none = CorsPolicy::default();
+        let cases: &[Case] = &[
+            (
+                "no origin or referer",
+                std::slice::from_ref(&host),
+                &[],
+                true,
+            ),
+            (
+                "same host",
+                &[host.clone(), (ORIGIN, "https://app.example.com")],
+                &[],
+                true,
+            ),
+            (
+                "other host",
+                &[host.clone(), (ORIGIN, "https://evil.example")],
+                &[],
+                false,
+            ),
+            (
+                "other port",
+                &[host.clone(), (ORIGIN, "https://app.example.com:8443")],
+                &[],
+                false,
+            ),
+            ("null origin", &[host.clone(), (ORIGIN, "null")], &[], false),
+            (
+                "null origin with a same-host referer",
+                &[
+                    host.clone(),
+                    (ORIGIN, "null"),
+                    (REFERER, "https://app.example.com/login.html"),
+                ],
+                &[],
+                false,
+            ),
+            (
+                "referer fallback",
+                &[
+                    host.clone(),
+                    (REFERER, "https://app.example.com/login.html?x=1"),
+                ],
+                &[],
+                true,
+            ),
+            (
+                "cross-site referer",
+                &[
+                    host.clone(),
+                    (REFERER, "https://evil.example/app.example.com/"),
+                ],
+                &[],
+                false,
+            ),
+            (
+                "referer without a scheme",
+                &[host.clone(), (REFERER, "app.example.com/login.html")],
+                &[],
+                false,
+            ),
+            (
+                "trusted origin",
+                &[host.clone(), (ORIGIN, "https://admin.example.com")],
+                &["https://admin.example.com"],
+                true,
+            ),
+            (
+                "trusted origin via referer",
+                &[host.clone(), (REFERER, "https://admin.example.com/users")],
+                &["https://admin.example.com"],
+                true,
+            ),
+            (
+                "no host",
+                &[(ORIGIN, "https://app.example.com")],
+                &[],
+                false,
+            ),
+        ];
+        for (name, pairs, trusted, ok) in cases {
+            assert_eq!(check(pairs, trusted, &none), *ok, "{name}");
+        }
+    }
+
+    #[test]
+    fn check_origin_accepts_credentialed_cors_origins_only() {
+        let host = (HOST, "api.example.com");
+        let origin = (ORIGIN, "https://app.example.com");
+        let mut cors = CorsPolicy {
+            origins: vec!["https://*.example.com".parse::<OriginPattern>().unwrap()],
+            ..CorsPolicy::default()
+        };
+        assert!(!check(&[host.clone(), origin.clone()], &[], &cors));
+        cors.credentials = true;
+        assert!(check(&[host.clone(), origin.clone()], &[], &cors));
+        let evil = (ORIGIN, "https://evil-example.com");
+        assert!(!check(&[host, evil], &[], &cors));
+    }
+}
@@ -0,0 +1,92 @@
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
+use axum::http::{Request, request::Parts};
+use axum::response::{IntoResponse, Response};
+use futures::future::{Either, Ready, ready};
+
+use super::ErrorResponse;
+use crate::{Failure, entity};
+
+/// Extracts the [`entity::Principal`] attached by the authentication middleware.
+#[derive(Debug, Clone)]
+pub struct Authenticated(pub entity::Principal);
+
+impl<S> FromRequestParts<S> for Authenticated
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let principal = parts
+            .extensions
+            .get::<entity::Principal>()
+            .cloned()
+            .ok_or_else(|| Failure::unauthorized("Unauthenticated"))?;
+        Ok(Self(principal))
+    }
+}
+
+impl<S> OptionalFromRequestParts<S> for Authenticated
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let principal = parts.extensions.get::<entity::Principal>().cloned();
+        Ok(principal.map(Self))
+    }
+}
+
+/// Layer rejecting requests whose [`entity::Principal`] lacks the given permission,
+/// e.g. `get(handler).route_layer(RequirePermission("users:write"))`.
+#[derive(Debug, Clone, Copy)]
+pub struct RequirePermission(pub &'static str);
+
+impl<S> tower::Layer<S> for RequirePermission {
+    type Service = RequirePermissionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermissionService {
+            inner,
+            permission: self.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequirePermissionService<S> {
+    inner: S,
+    permission: &'static str,
+}
+
+impl<S, B> tower::Service<Request<B>> for RequirePermissionService<S>
+where
+    S: tower::Service<Request<B>, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let failure = match req.extensions().get::<entity::Principal>() {
+            Some(p) if p.has_permission(self.permission) => {
+                return Either::Left(self.inner.call(req));
+            }
+            Some(_) => Failure::forbidden(format!("Missing permission {}", self.permission)),
+            None => Failure::unauthorized("Unauthenticated"),
+        };
+        let response = ErrorResponse::from(failure).into_response();
+        Either::Right(ready(Ok(response)))
+    }
+}
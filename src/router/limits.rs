@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use tower_http::timeout::TimeoutLayer;
+
+/// Bounds on the time, size and number of API requests.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub request_timeout: Duration,
+    /// For routes hashing a password, which may queue behind other logins.
+    pub hashing_timeout: Duration,
+    /// Bytes of a request body; the API only takes small forms and JSON.
+    pub body_limit: usize,
+    /// API requests handled at once; the rest wait, up to their timeout.
+    pub concurrency_limit: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(10),
+            hashing_timeout: Duration::from_secs(30),
+            body_limit: 16 * 1024,
+            concurrency_limit: 512,
+        }
+    }
+}
+
+/// Answers 503 once `timeout` passes, as the server rather than the client is too slow.
+pub(super) fn timeout(timeout: Duration) -> TimeoutLayer {
+    TimeoutLayer::with_status_code(StatusCode::SERVICE_UNAVAILABLE, timeout)
+}
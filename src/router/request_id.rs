@@ -0,0 +1,54 @@
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+pub const HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The ID of the request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Accepts IDs from upstream proxies only if they fit the audit log and are safe to log.
+fn is_valid(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// Takes a well-formed `X-Request-Id` or generates one, then exposes it to the request span, to
+/// handlers through the header and [`current`], and to the client on the response.
+pub async fn propagate(mut req: Request, next: Next) -> Response {
+    let incoming = req
+        .headers()
+        .get(&HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid(id));
+    let id = incoming.map_or_else(|| uuid::Uuid::new_v4().to_string(), str::to_string);
+    let value = HeaderValue::from_str(&id).expect("validated or generated ids are header values");
+    req.headers_mut().insert(HEADER, value.clone());
+    let mut res = REQUEST_ID.scope(id, next.run(req)).await;
+    res.headers_mut().insert(HEADER, value);
+    res
+}
+
+/// Opens the span of a request, carrying its ID; see [`propagate`].
+pub fn make_span(req: &Request) -> tracing::Span {
+    let id = req
+        .headers()
+        .get(&HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id = %id,
+    )
+}
@@ -0,0 +1,313 @@
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, header};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use super::{AppState, StateRequirements};
+
+/// Security headers for a response; an empty value leaves that header out.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityHeaders {
+    pub content_security_policy: String,
+    /// Sources allowed to frame pages, added to the CSP and mirrored to `X-Frame-Options`.
+    pub frame_ancestors: String,
+    pub strict_transport_security: String,
+    /// Sends `X-Content-Type-Options: nosniff`.
+    pub nosniff: bool,
+    pub referrer_policy: String,
+    pub cache_control: String,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_security_policy:
+                "default-src 'self'; object-src 'none'; base-uri 'self'; form-action 'self'"
+                    .to_string(),
+            frame_ancestors: "'none'".to_string(),
+            strict_transport_security: "max-age=31536000".to_string(),
+            nosniff: true,
+            referrer_policy: "no-referrer".to_string(),
+            cache_control: String::new(),
+        }
+    }
+}
+
+/// Replaces the headers it sets for paths starting with `path`, relative to the path prefix.
+#[must_use]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteHeaders {
+    pub path: String,
+    pub content_security_policy: Option<String>,
+    pub frame_ancestors: Option<String>,
+    pub strict_transport_security: Option<String>,
+    pub nosniff: Option<bool>,
+    pub referrer_policy: Option<String>,
+    pub cache_control: Option<String>,
+}
+
+/// The security headers of every response, unless the handler set them itself.
+#[must_use]
+#[derive(Debug, Clone)]
+pub struct SecurityPolicy {
+    pub headers: SecurityHeaders,
+    /// Applied in order to matching paths, so that later routes win.
+    pub routes: Vec<RouteHeaders>,
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        Self {
+            headers: SecurityHeaders::default(),
+            routes: vec![Self::api_route()],
+        }
+    }
+}
+
+impl SecurityPolicy {
+    /// Keeps API responses, which carry tokens and user data, out of every cache.
+    pub fn api_route() -> RouteHeaders {
+        RouteHeaders {
+            path: "/api/".to_string(),
+            cache_control: Some("no-store".to_string()),
+            ..RouteHeaders::default()
+        }
+    }
+
+    /// Checks that every header value is sendable, for the defaults and each route.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, value) in self.headers.headers() {
+            HeaderValue::from_str(&value).map_err(|_| anyhow::anyhow!("invalid {name} value"))?;
+        }
+        for route in &self.routes {
+            anyhow::ensure!(
+                route.path.starts_with('/'),
+                "{} must start with /",
+                route.path
+            );
+            for (name, value) in self.headers_for(&route.path).headers() {
+                HeaderValue::from_str(&value)
+                    .map_err(|_| anyhow::anyhow!("{}: invalid {name} value", route.path))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the headers for `path`, relative to the path prefix.
+    pub fn headers_for(&self, path: &str) -> SecurityHeaders {
+        let mut headers = self.headers.clone();
+        let routes = self.routes.iter().filter(|r| path.starts_with(&r.path));
+        for route in routes {
+            let replace = |value: &mut String, with: &Option<String>| {
+                if let Some(with) = with {
+                    value.clone_from(with);
+                }
+            };
+            replace(
+                &mut headers.content_security_policy,
+                &route.content_security_policy,
+            );
+            replace(&mut headers.frame_ancestors, &route.frame_ancestors);
+            replace(
+                &mut headers.strict_transport_security,
+                &route.strict_transport_security,
+            );
+            headers.nosniff = route.nosniff.unwrap_or(headers.nosniff);
+            replace(&mut headers.referrer_policy, &route.referrer_policy);
+            replace(&mut headers.cache_control, &route.cache_control);
+        }
+        headers
+    }
+}
+
+impl SecurityHeaders {
+    /// The headers to send, with `frame-ancestors` folded into the CSP.
+    #[must_use]
+    pub fn headers(&self) -> Vec<(HeaderName, String)> {
+        let mut csp = self
+            .content_security_policy
+            .trim_end_matches(';')
+            .to_string();
+        if !self.frame_ancestors.is_empty() {
+            if !csp.is_empty() {
+                csp.push_str("; ");
+            }
+            csp.push_str("frame-ancestors ");
+            csp.push_str(&self.frame_ancestors);
+        }
+        // for browsers predating frame-ancestors
+        let frame_options = match self.frame_ancestors.as_str() {
+            "'none'" => "DENY",
+            "'self'" => "SAMEORIGIN",
+            _ => "",
+        };
+        let nosniff = if self.nosniff { "nosniff" } else { "" };
+        [
+            (header::CONTENT_SECURITY_POLICY, csp),
+            (header::X_FRAME_OPTIONS, frame_options.to_string()),
+            (
+                header::STRICT_TRANSPORT_SECURITY,
+                self.strict_transport_security.clone(),
+            ),
+            (header::X_CONTENT_TYPE_OPTIONS, nosniff.to_string()),
+            (header::REFERRER_POLICY, self.referrer_policy.clone()),
+            (header::CACHE_CONTROL, self.cache_control.clone()),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+    }
+}
+
+impl<S> AppState<S>
+where
+    S: StateRequirements,
+{
+    pub(super) async fn security_headers(
+        State(state): State<Self>,
+        req: Request,
+        next: Next,
+    ) -> Response {
+        let headers = state.security().headers_for(req.uri().path());
+        let mut res = next.run(req).await;
+        for (name, value) in headers.headers() {
+            // values are validated with the configuration
+            let Ok(value) = HeaderValue::from_str(&value) else {
+                continue;
+            };
+            res.headers_mut().entry(name).or_insert(value);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(path: &str, cache_control: &str) -> RouteHeaders {
+        RouteHeaders {
+            path: path.to_string(),
+            cache_control: Some(cache_control.to_string()),
+            ..RouteHeaders::default()
+        }
+    }
+
+    fn policy(routes: Vec<RouteHeaders>) -> SecurityPolicy {
+        SecurityPolicy {
+            headers: SecurityHeaders::default(),
+            routes,
+        }
+    }
+
+    fn get(headers: &SecurityHeaders, name: &HeaderName) -> Option<String> {
+        headers
+            .headers()
+            .into_iter()
+            .find_map(|(n, value)| (n == name).then_some(value))
+    }
+
+    #[test]
+    fn headers_for_applies_matching_routes_in_order() {
+        let assets = route("/api/assets/", "public, max-age=60");
+        let api = SecurityPolicy::api_route();
+        let cases = [
+            (
+                vec![api.clone(), assets.clone()],
+                "/api/me",
+                Some("no-store"),
+            ),
+            (
+                vec![api.clone(), assets.clone()],
+                "/api/assets/logo.svg",
+                Some("public, max-age=60"),
+            ),
+            // later routes win, however specific the earlier ones are
+            (
+                vec![assets.clone(), api.clone()],
+                "/api/assets/logo.svg",
+                Some("no-store"),
+            ),
+            (vec![api.clone(), assets], "/index.html", None),
+            (vec![api.clone()], "/api", None),
+            (vec![api], "/", None),
+        ];
+        for (routes, path, expected) in cases {
+            let headers = policy(routes).headers_for(path);
+            let cache = get(&headers, &header::CACHE_CONTROL);
+            assert_eq!(cache.as_deref(), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn headers_for_keeps_what_later_routes_leave_unset() {
+        let first = RouteHeaders {
+            path: "/".to_string(),
+            referrer_policy: Some("same-origin".to_string()),
+            nosniff: Some(false),
+            ..RouteHeaders::default()
+        };
+        let second = RouteHeaders {
+            strict_transport_security: Some(String::new()),
+            ..route("/api/", "no-store")
+        };
+        let headers = policy(vec![first, second]).headers_for("/api/me");
+        assert_eq!(headers.referrer_policy, "same-origin");
+        assert!(!headers.nosniff);
+        assert_eq!(headers.cache_control, "no-store");
+        assert_eq!(
+            headers.content_security_policy,
+            SecurityHeaders::default().content_security_policy
+        );
+        // an empty value drops the header
+        assert_eq!(get(&headers, &header::STRICT_TRANSPORT_SECURITY), None);
+        assert_eq!(get(&headers, &header::X_CONTENT_TYPE_OPTIONS), None);
+    }
+
+    #[test]
+    fn frame_ancestors_fold_into_the_csp() {
+        let cases = [
+            (
+                "default-src 'self';",
+                "'none'",
+                Some("default-src 'self'; frame-ancestors 'none'"),
+                Some("DENY"),
+            ),
+            (
+                "default-src 'self'",
+                "'self'",
+                Some("default-src 'self'; frame-ancestors 'self'"),
+                Some("SAMEORIGIN"),
+            ),
+            (
+                "",
+                "https://portal.example.com",
+                Some("frame-ancestors https://portal.example.com"),
+                None,
+            ),
+            ("default-src 'self'", "", Some("default-src 'self'"), None),
+            ("", "", None, None),
+        ];
+        for (csp, frame_ancestors, expected_csp, expected_frame_options) in cases {
+            let headers = SecurityHeaders {
+                content_security_policy: csp.to_string(),
+                frame_ancestors: frame_ancestors.to_string(),
+                ..SecurityHeaders::default()
+            };
+            let name = format!("{csp:?} {frame_ancestors:?}");
+            let csp = get(&headers, &header::CONTENT_SECURITY_POLICY);
+            assert_eq!(csp.as_deref(), expected_csp, "{name}");
+            let frame_options = get(&headers, &header::X_FRAME_OPTIONS);
+            assert_eq!(frame_options.as_deref(), expected_frame_options, "{name}");
+        }
+    }
+
+    #[test]
+    fn validate_checks_every_route() {
+        assert!(SecurityPolicy::default().validate().is_ok());
+        assert!(policy(vec![route("api/", "no-store")]).validate().is_err());
+        let split = route("/api/", "no-store\r\nSet-Cookie: a=b");
+        assert!(policy(vec![split]).validate().is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+const REDACTED: &str = "[redacted]";
+
+/// A value kept out of logs and responses: `Debug`, `Display` and `Serialize` print a placeholder.
+///
+/// Deserializes transparently, so request bodies can carry it directly.
+#[must_use]
+#[derive(Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T = String>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// The wrapped value; take care not to log it.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl Secret<String> {
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_redacted_but_read_transparently() {
+        let secret: Secret = serde_json::from_str(r#""correct horse""#).unwrap();
+        assert_eq!(secret.as_str(), "correct horse");
+        assert_eq!(format!("{secret}"), REDACTED);
+        assert_eq!(format!("{secret:?}"), REDACTED);
+        assert_eq!(
+            serde_json::to_string(&secret).unwrap(),
+            format!("\"{REDACTED}\"")
+        );
+    }
+
+    #[test]
+    fn params_do_not_leak_passwords() {
+        let register = crate::entity::RegisterUserParams {
+            display_id: "alice".to_string(),
+            name: "Alice".to_string(),
+            raw_password: Secret::from("correct horse"),
+        };
+        let update = crate::entity::UpdateUserPasswordParams {
+            user_id: crate::entity::UserId(uuid::Uuid::nil()),
+            new_raw: Secret::from("battery staple"),
+        };
+        let logged = [
+            format!("{register:?}"),
+            serde_json::to_string(&register).unwrap(),
+            format!("{update:?}"),
+            serde_json::to_string(&update).unwrap(),
+        ];
+        for logged in logged {
+            assert!(!logged.contains("horse"), "{logged}");
+            assert!(!logged.contains("staple"), "{logged}");
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Log output and optional OTLP trace export.
+
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config::{LogFormat, TelemetryConfig};
+
+/// Flushes exported spans when dropped; hold it until the process exits.
+#[must_use]
+pub struct Guard {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
+}
+
+/// Installs the global subscriber, filtered by `RUST_LOG` or else `default_filter`.
+pub fn init(config: &TelemetryConfig, default_filter: &str) -> anyhow::Result<Guard> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| default_filter.into());
+    let json = config.log_format == LogFormat::Json;
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then(|| tracing_subscriber::fmt::layer().json()));
+
+    #[cfg(feature = "otlp")]
+    {
+        let provider = config
+            .otlp_endpoint
+            .as_deref()
+            .map(|endpoint| otlp_provider(endpoint, &config.service_name))
+            .transpose()?;
+        let layer = provider.as_ref().map(|provider| {
+            use opentelemetry::trace::TracerProvider;
+
+            let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+            tracing_opentelemetry::layer().with_tracer(tracer)
+        });
+        registry.with(layer).try_init()?;
+        Ok(Guard { provider })
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        anyhow::ensure!(
+            config.otlp_endpoint.is_none(),
+            "telemetry.otlp_endpoint is set, but this build lacks the otlp feature"
+        );
+        registry.try_init()?;
+        Ok(Guard {})
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_provider(
+    endpoint: &str,
+    service_name: &str,
+) -> anyhow::Result<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use anyhow::Context;
+    use opentelemetry_otlp::WithExportConfig;
+
+    // the base URL, as in `OTEL_EXPORTER_OTLP_ENDPOINT`
+    let endpoint = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .context("Failed to build the OTLP exporter")?;
+    let resource = opentelemetry_sdk::Resource::builder()
+        .with_service_name(service_name.to_string())
+        .build();
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+    Ok(provider)
+}
@@ -0,0 +1,160 @@
+//! HTTPS with certificates reloaded in place, and a plain HTTP redirect to it.
+//!
+//! Serve the acceptor through a [`crate::listener::PreparedListener`].
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum::extract::Request;
+use axum::http::{StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::TlsConfig;
+
+/// How often the certificate files are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Hands every handshake the latest certificate; a reload only affects new connections.
+#[derive(Debug)]
+struct CertResolver {
+    cert: PathBuf,
+    key: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.current.read().expect("not poisoned")))
+    }
+}
+
+impl CertResolver {
+    fn new(config: &TlsConfig, provider: Arc<CryptoProvider>) -> anyhow::Result<Self> {
+        let current = load(&config.cert, &config.key, &provider)?;
+        Ok(Self {
+            cert: config.cert.clone(),
+            key: config.key.clone(),
+            provider,
+            current: RwLock::new(Arc::new(current)),
+        })
+    }
+
+    /// Swaps in the certificate on disk, keeping the current one if it fails to load.
+    fn reload(&self) {
+        match load(&self.cert, &self.key, &self.provider) {
+            Ok(loaded) => {
+                *self.current.write().expect("not poisoned") = Arc::new(loaded);
+                tracing::info!(cert = %self.cert.display(), "Reloaded the TLS certificate");
+            }
+            Err(e) => tracing::error!("Failed to reload the TLS certificate: {e:#}"),
+        }
+    }
+
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.cert)?, modified(&self.key)?))
+    }
+
+    /// Reloads whenever either file changes, or on SIGHUP.
+    async fn watch(self: Arc<Self>) {
+        #[cfg(unix)]
+        let mut hangup = {
+            use tokio::signal::unix::{SignalKind, signal};
+            signal(SignalKind::hangup())
+                .inspect_err(|e| tracing::error!("{e}"))
+                .ok()
+        };
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last = self.modified();
+        loop {
+            #[cfg(unix)]
+            let hangup = async {
+                match &mut hangup {
+                    Some(hangup) => hangup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+            tokio::select! {
+                _ = interval.tick() => {
+                    let modified = self.modified();
+                    if modified == last {
+                        continue;
+                    }
+                    last = modified;
+                }
+                _ = hangup => {
+                    last = self.modified();
+                }
+            }
+            self.reload();
+        }
+    }
+}
+
+fn load(cert: &Path, key: &Path, provider: &CryptoProvider) -> anyhow::Result<CertifiedKey> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .with_context(|| format!("Failed to read {}", cert.display()))?;
+    anyhow::ensure!(!chain.is_empty(), "No certificate in {}", cert.display());
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("Failed to read {}", key.display()))?;
+    CertifiedKey::from_der(chain, key, provider).context("The key does not match the certificate")
+}
+
+/// Loads the certificate, then keeps it up to date for as long as the acceptor is in use.
+pub fn acceptor(config: &TlsConfig) -> anyhow::Result<TlsAcceptor> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let resolver = Arc::new(CertResolver::new(config, Arc::clone(&provider))?);
+    let mut server_config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .context("Failed to configure TLS")?
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::clone(&resolver) as Arc<dyn ResolvesServerCert>);
+    // axum is built without HTTP/2
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    tokio::spawn(resolver.watch());
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Redirects every request to the same host and path over HTTPS on `https_port`.
+pub fn redirect_router(https_port: u16) -> axum::Router {
+    axum::Router::new().fallback(move |req: Request| redirect(https_port, req))
+}
+
+async fn redirect(https_port: u16, req: Request) -> Response {
+    use tower::ServiceExt;
+
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|host| host.parse::<axum::http::uri::Authority>().ok());
+    let Some(host) = host else {
+        return (StatusCode::BAD_REQUEST, "Missing or invalid Host header").into_response();
+    };
+    let authority = match https_port {
+        443 => host.host().to_string(),
+        port => format!("{}:{port}", host.host()),
+    };
+    let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+    let Ok(uri) = format!("https://{authority}{path}").parse::<Uri>() else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let redirect = tower_http::services::Redirect::<axum::body::Body>::permanent(uri);
+    match redirect.oneshot(req).await {
+        Ok(res) => res.into_response(),
+        Err(never) => match never {},
+    }
+}
@@ -4,8 +4,8 @@ use anyhow::Context;
 use jsonwebtoken as jwt;
 use serde::{Deserialize, Serialize};
 
-use crate::Failure;
-use crate::entity::{Credential, UserId};
+use crate::entity::{Credential, Permission, Principal, Session, SessionId, UserId};
+use crate::{Failure, Secret};
 
 #[derive(Debug, Clone, Serialize)]
 struct EncodeClaims<'a> {
@@ -14,6 +14,9 @@ struct EncodeClaims<'a> {
     #[serde(borrow = "'a")]
     iss: &'a str,
     sub: UserId,
+    jti: SessionId,
+    roles: Vec<&'a str>,
+    perms: Vec<&'a Permission>,
 }
 
 #[allow(unused)]
@@ -23,6 +26,11 @@ struct DecodeClaims {
     exp: u64,
     iss: String,
     sub: UserId,
+    jti: SessionId,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    perms: Vec<Permission>,
 }
 
 #[must_use]
@@ -31,52 +39,193 @@ pub struct Jwt {
     algorithm: jwt::Algorithm,
     issuer: String,
     lifetime: Duration,
-    #[allow(unused)]
-    raw_key: String,
+    raw_key: Secret,
     enc_key: jwt::EncodingKey,
     dec_key: jwt::DecodingKey,
     validation: jwt::Validation,
 }
 
+/// How stale `last_used_at` may get before [`Jwt`] writes it back on a credential check.
+const LAST_USED_RESOLUTION: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+
+/// Fills the `user_id` and `session_id` fields of the current span once they are known.
+fn record_ids(user_id: Option<UserId>, session_id: Option<SessionId>) {
+    let span = tracing::Span::current();
+    if let Some(UserId(id)) = user_id {
+        span.record("user_id", tracing::field::display(id));
+    }
+    if let Some(SessionId(id)) = session_id {
+        span.record("session_id", tracing::field::display(id));
+    }
+}
+
+/// Maps a missing record to the rejection of an invalid credential.
+fn invalid_if_not_found(failure: Failure) -> Failure {
+    use crate::error::RejectKind;
+
+    match failure {
+        Failure::Reject(r) if r.kind() == RejectKind::NotFound => {
+            Failure::unauthorized("Invalid credential")
+        }
+        e => e,
+    }
+}
+
+impl Jwt {
+    fn decode(&self, credential: &Credential) -> Result<DecodeClaims, Failure> {
+        let Credential(token) = credential;
+        let key = &self.dec_key;
+        let validation = &self.validation;
+        let token = jwt::decode(token, key, validation).map_err(|e| {
+            tracing::debug!(error = %e, "Failed to decode JWT");
+            Failure::unauthorized("Invalid credential")
+        })?;
+        Ok(token.claims)
+    }
+
+    /// Signs and verifies a throwaway token, proving the key can still issue credentials.
+    pub fn check_signing_key(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.raw_key.as_str().is_empty(), "Signing key is empty");
+        let iat = jwt::get_current_timestamp();
+        let claims = EncodeClaims {
+            iat,
+            exp: iat + 60,
+            iss: &self.issuer,
+            sub: UserId(uuid::Uuid::nil()),
+            jti: SessionId(uuid::Uuid::nil()),
+            roles: Vec::new(),
+            perms: Vec::new(),
+        };
+        let header = jwt::Header::new(self.algorithm);
+        let token = jwt::encode(&header, &claims, &self.enc_key).context("Failed to sign")?;
+        jwt::decode::<DecodeClaims>(&token, &self.dec_key, &self.validation)
+            .context("Failed to verify")?;
+        Ok(())
+    }
+}
+
 impl<Context> crate::entity::CredentialManager<Context> for Jwt
 where
-    Context: Send + Sync,
+    Context: crate::entity::ProvideSessionRepository,
 {
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, session_id))]
     async fn make_credential(
         &self,
-        _ctx: Context,
+        ctx: Context,
         params: crate::entity::MakeCredentialParams,
     ) -> Result<Credential, Failure> {
+        let crate::entity::MakeCredentialParams {
+            user_id,
+            roles,
+            user_agent,
+            ip,
+        } = params;
         let iat = jwt::get_current_timestamp();
         let exp = iat + self.lifetime.as_secs();
+        let expires_at = i64::try_from(exp)
+            .ok()
+            .and_then(|exp| chrono::DateTime::from_timestamp(exp, 0))
+            .context("JWT expiry out of range")?;
+        let params = crate::entity::CreateSessionParams {
+            user_id,
+            user_agent,
+            ip,
+            expires_at,
+        };
+        let session = ctx.create_session(params).await?;
+        record_ids(None, Some(session.id));
         let iss = &self.issuer;
-        let sub = params.user_id;
-        let claims = EncodeClaims { iat, exp, iss, sub };
+        let role_names = roles.iter().map(|r| r.name.as_str()).collect();
+        let mut perms: Vec<_> = roles.iter().flat_map(|r| &r.permissions).collect();
+        perms.sort_unstable();
+        perms.dedup();
+        let claims = EncodeClaims {
+            iat,
+            exp,
+            iss,
+            sub: user_id,
+            jti: session.id,
+            roles: role_names,
+            perms,
+        };
         let header = jwt::Header::new(self.algorithm);
         let key = &self.enc_key;
         let encoded = jwt::encode(&header, &claims, key).context("Failed to encode JWT")?;
         Ok(Credential(encoded))
     }
 
-    async fn revoke_credential(
-        &self,
-        _ctx: Context,
-        _credential: Credential,
-    ) -> Result<(), Failure> {
-        todo!()
+    #[tracing::instrument(skip_all, fields(user_id, session_id))]
+    async fn revoke_credential(&self, ctx: Context, credential: Credential) -> Result<(), Failure> {
+        let DecodeClaims { sub, jti, .. } = self.decode(&credential)?;
+        record_ids(Some(sub), Some(jti));
+        let params = crate::entity::DeleteSessionParams {
+            user_id: sub,
+            id: jti,
+        };
+        ctx.delete_session(params)
+            .await
+            .map_err(invalid_if_not_found)
     }
 
+    #[tracing::instrument(skip_all, fields(user_id, session_id))]
     async fn check_credential(
         &self,
-        _ctx: Context,
+        ctx: Context,
         credential: Credential,
-    ) -> Result<UserId, Failure> {
-        let Credential(token) = credential;
-        let key = &self.dec_key;
-        let validation = &self.validation;
-        let token = jwt::decode(&token, key, validation).context("Failed to decode JWT")?;
-        let DecodeClaims { sub, .. } = token.claims;
-        Ok(sub)
+    ) -> Result<Principal, Failure> {
+        let DecodeClaims {
+            sub,
+            jti,
+            roles,
+            perms,
+            ..
+        } = self.decode(&credential)?;
+        record_ids(Some(sub), Some(jti));
+        let crate::entity::SessionWithUserStatus {
+            session,
+            user_status,
+        } = ctx
+            .get_session_with_user_status(jti)
+            .await
+            .map_err(invalid_if_not_found)?;
+        if session.user_id != sub {
+            return Err(Failure::unauthorized("Invalid credential"));
+        }
+        user_status.ensure_active()?;
+        // only write back once per resolution so that checks stay read-only
+        let now = chrono::Utc::now();
+        if now - session.last_used_at >= LAST_USED_RESOLUTION {
+            let params = crate::entity::TouchSessionParams {
+                id: session.id,
+                used_at: now,
+            };
+            ctx.touch_session(params).await?;
+        }
+        Ok(Principal {
+            user_id: sub,
+            session_id: jti,
+            roles,
+            permissions: perms,
+        })
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %user_id.0))]
+    async fn get_sessions(&self, ctx: Context, user_id: UserId) -> Result<Vec<Session>, Failure> {
+        ctx.get_user_sessions(user_id).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %params.user_id.0, session_id = %params.id.0))]
+    async fn revoke_session(
+        &self,
+        ctx: Context,
+        params: crate::entity::DeleteSessionParams,
+    ) -> Result<(), Failure> {
+        ctx.delete_session(params).await
+    }
+
+    #[tracing::instrument(skip_all, fields(user_id = %user_id.0))]
+    async fn revoke_all_sessions(&self, ctx: Context, user_id: UserId) -> Result<u64, Failure> {
+        ctx.delete_user_sessions(user_id).await
     }
 }
 
@@ -105,14 +254,14 @@ impl Builder {
 }
 
 impl<Key, Issuer, Lifetime> Builder<Key, Issuer, Lifetime> {
-    pub fn key(self, value: &str) -> Builder<String, Issuer, Lifetime> {
+    pub fn key(self, value: &str) -> Builder<Secret, Issuer, Lifetime> {
         let Self {
             key: _,
             issuer,
             lifetime,
         } = self;
         Builder {
-            key: value.to_string(),
+            key: Secret::from(value),
             issuer,
             lifetime,
         }
@@ -145,7 +294,7 @@ impl<Key, Issuer, Lifetime> Builder<Key, Issuer, Lifetime> {
     }
 }
 
-impl Builder<String, String, Duration> {
+impl Builder<Secret, String, Duration> {
     pub fn build(self) -> Jwt {
         let Self {
             key: raw_key,
@@ -153,8 +302,8 @@ impl Builder<String, String, Duration> {
             lifetime,
         } = self;
         let algorithm = jwt::Algorithm::HS256;
-        let enc_key = jwt::EncodingKey::from_secret(raw_key.as_bytes());
-        let dec_key = jwt::DecodingKey::from_secret(raw_key.as_bytes());
+        let enc_key = jwt::EncodingKey::from_secret(raw_key.as_str().as_bytes());
+        let dec_key = jwt::DecodingKey::from_secret(raw_key.as_str().as_bytes());
         let validation = jwt::Validation::new(algorithm);
         Jwt {
             algorithm,
@@ -0,0 +1,58 @@
+//! Account self-management through the router: profile updates follow the registration rules.
+
+mod support;
+
+use axum::http::StatusCode;
+use login_with_axum::entity::UserStatus;
+use login_with_axum::password::{Algorithm, PasswordHasher};
+use serde_json::json;
+
+#[tokio::test]
+async fn profile_updates() {
+    let state = support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+    state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    state
+        .add_user("bob", "correct horse", UserStatus::Active)
+        .await;
+    // from before the rules, as add_user skips them
+    state
+        .add_user("carol!", "correct horse", UserStatus::Active)
+        .await;
+    let router = state.into_router();
+    let csrf = support::csrf(&router).await;
+    let session =
+        support::session_cookie(&support::login(&router, &csrf, "alice", "correct horse").await);
+
+    let long = "a".repeat(33);
+    let cases = [
+        (json!({ "display_id": "bob" }), StatusCode::CONFLICT),
+        (json!({ "display_id": "" }), StatusCode::BAD_REQUEST),
+        (json!({ "display_id": long }), StatusCode::BAD_REQUEST),
+        (
+            json!({ "display_id": "alice smith" }),
+            StatusCode::BAD_REQUEST,
+        ),
+        // an invalid display id is refused before it is looked up
+        (json!({ "display_id": "carol!" }), StatusCode::BAD_REQUEST),
+        (json!({ "name": " " }), StatusCode::BAD_REQUEST),
+        (json!({ "name": "Alice\nSmith" }), StatusCode::BAD_REQUEST),
+        (json!({ "name": long }), StatusCode::BAD_REQUEST),
+        // keeping one's own display id is no conflict
+        (json!({ "display_id": "alice" }), StatusCode::OK),
+        (
+            json!({ "display_id": "alice.smith", "name": "Alice Smith" }),
+            StatusCode::OK,
+        ),
+    ];
+    for (body, status) in cases {
+        let res = support::send_json(&router, "PATCH", "/api/me", &csrf, &session, &body).await;
+        assert_eq!(res.status(), status, "{body}");
+    }
+
+    let res = support::get(&router, "/api/me", &session).await;
+    let me: serde_json::Value = serde_json::from_str(&support::text(res).await).unwrap();
+    assert_eq!(me["display_id"], "alice.smith");
+    assert_eq!(me["name"], "Alice Smith");
+}
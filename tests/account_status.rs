@@ -0,0 +1,111 @@
+//! Account status transitions: what each status allows, and when a deleted account's display id
+//! is released.
+
+mod support;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use login_with_axum::Secret;
+use login_with_axum::entity::{
+    GetUserParams, ProvideUserRegistry as _, RegisterUserParams, SetUserStatusParams, User,
+    UserRepository as _, UserStatus,
+};
+use login_with_axum::error::{Failure, RejectKind};
+use login_with_axum::password::{Algorithm, PasswordHasher};
+
+async fn state() -> (Arc<support::MemoryState>, User) {
+    let state = support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+    let alice = state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    (Arc::new(state), alice)
+}
+
+async fn set_status(state: &support::MemoryState, user: &User, status: UserStatus) -> User {
+    let params = SetUserStatusParams {
+        id: user.id,
+        status,
+    };
+    state.set_user_status(params).await.expect("set status")
+}
+
+fn register_alice() -> RegisterUserParams {
+    RegisterUserParams {
+        display_id: "alice".to_string(),
+        name: "Alice".to_string(),
+        raw_password: Secret::from("battery staple"),
+    }
+}
+
+fn reject_kind<T>(res: Result<T, Failure>) -> Option<RejectKind> {
+    match res {
+        Err(Failure::Reject(r)) => Some(r.kind()),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn status_transitions() {
+    let (state, alice) = state().await;
+    let router = login_with_axum::make_router(Arc::clone(&state));
+    let csrf = support::csrf(&router).await;
+    let login = || support::login(&router, &csrf, "alice", "correct horse");
+
+    set_status(&state, &alice, UserStatus::Suspended).await;
+    assert_eq!(login().await.status(), StatusCode::FORBIDDEN);
+    set_status(&state, &alice, UserStatus::Active).await;
+    assert_eq!(login().await.status(), StatusCode::SEE_OTHER);
+
+    let deleted = set_status(&state, &alice, UserStatus::Deleted).await;
+    assert_eq!(deleted.status, UserStatus::Deleted);
+    assert_eq!(login().await.status(), StatusCode::UNAUTHORIZED);
+    // the display id stays taken until the account is purged
+    let res = state.register_user(register_alice()).await;
+    assert_eq!(reject_kind(res), Some(RejectKind::Conflict));
+
+    let purged = state
+        .purge_deleted_users(chrono::Utc::now() + chrono::Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(purged, 1);
+    let again = state.register_user(register_alice()).await.unwrap();
+    assert_ne!(again.id, alice.id);
+    assert_eq!(again.status, UserStatus::Active);
+}
+
+#[tokio::test]
+async fn deleted_users_are_purged_periodically() {
+    let (state, alice) = state().await;
+    let bob = state
+        .add_user("bob", "correct horse", UserStatus::Active)
+        .await;
+    set_status(&state, &bob, UserStatus::Deleted).await;
+
+    let purge = tokio::spawn({
+        let state = Arc::clone(&state);
+        async move {
+            let interval = Duration::from_millis(10);
+            login_with_axum::purge_deleted_users_periodically(&*state, Duration::ZERO, interval)
+                .await;
+        }
+    });
+    let store = state.store();
+    let gone = async {
+        while store
+            .get_user((), GetUserParams::ById(bob.id))
+            .await
+            .is_ok()
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), gone)
+        .await
+        .expect("bob purged");
+    purge.abort();
+    // only deleted users go
+    let kept = store.get_user((), GetUserParams::ById(alice.id)).await;
+    assert_eq!(kept.unwrap().status, UserStatus::Active);
+}
@@ -0,0 +1,96 @@
+//! Checks that cookies are marked secure when a trusted proxy reports HTTPS or TLS is
+//! terminated in-process, even with `cookie.secure` off, and only then.
+
+mod support;
+
+use axum::body::Body;
+use axum::http::{Request, Response, StatusCode, header};
+use login_with_axum::entity::UserStatus;
+use login_with_axum::password::{Algorithm, PasswordHasher};
+use login_with_axum::proxy::TrustedProxies;
+use tower::ServiceExt as _;
+
+fn is_secure(res: &Response<Body>, name: &str) -> bool {
+    let cookie = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find(|v| v.starts_with(&format!("{name}=")))
+        .expect("cookie set");
+    cookie.split(';').any(|a| a.trim() == "Secure")
+}
+
+async fn router(trusted: &str) -> axum::Router {
+    let hasher = PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4);
+    let mut state = support::MemoryState::new(hasher);
+    state.cookie_policy.secure = false;
+    state.trusted_proxies =
+        TrustedProxies::new(vec![trusted.parse().unwrap()]).trust_unix_socket(true);
+    state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    state.into_router()
+}
+
+async fn csrf_token(router: &axum::Router, proto: &str) -> Response<Body> {
+    let req = Request::get("/api/csrf-token")
+        .header("x-forwarded-for", "203.0.113.7")
+        .header("x-forwarded-proto", proto)
+        .body(Body::empty())
+        .unwrap();
+    router.clone().oneshot(req).await.unwrap()
+}
+
+#[tokio::test]
+async fn cookies_are_secure_behind_an_https_proxy() {
+    // requests without a peer address count as coming through the trusted Unix socket
+    let router = router("127.0.0.1").await;
+    assert!(is_secure(
+        &csrf_token(&router, "https").await,
+        "session-csrf"
+    ));
+    assert!(!is_secure(
+        &csrf_token(&router, "http").await,
+        "session-csrf"
+    ));
+
+    let res = support::login(
+        &router,
+        &support::csrf(&router).await,
+        "alice",
+        "correct horse",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert!(!is_secure(&res, "session"));
+}
+
+#[tokio::test]
+async fn https_from_untrusted_clients_is_ignored() {
+    let router = router("127.0.0.1").await;
+    let req = Request::get("/api/csrf-token")
+        .header("x-forwarded-for", "203.0.113.7")
+        .header("x-forwarded-proto", "https")
+        .extension(axum::extract::ConnectInfo(
+            "198.51.100.9:5000".parse::<std::net::SocketAddr>().unwrap(),
+        ))
+        .body(Body::empty())
+        .unwrap();
+    let res = router.oneshot(req).await.unwrap();
+    assert!(!is_secure(&res, "session-csrf"));
+}
+
+#[tokio::test]
+async fn cookies_are_secure_over_in_process_tls() {
+    let router = router("127.0.0.1").await;
+    let req = Request::get("/api/csrf-token")
+        .extension(axum::extract::ConnectInfo(
+            "198.51.100.9:5000".parse::<std::net::SocketAddr>().unwrap(),
+        ))
+        .extension(login_with_axum::TlsConnection)
+        .body(Body::empty())
+        .unwrap();
+    let res = router.oneshot(req).await.unwrap();
+    assert!(is_secure(&res, "session-csrf"));
+}
@@ -0,0 +1,81 @@
+//! Login responses through the router: which failures clients can tell apart.
+
+mod support;
+
+use axum::http::StatusCode;
+use login_with_axum::entity::{AuditEvent, AuditEventKind, UserStatus};
+use login_with_axum::password::{Algorithm, PasswordHasher};
+
+#[tokio::test]
+async fn login_failures() {
+    let state = support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+    state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    state
+        .add_user("sam", "correct horse", UserStatus::Suspended)
+        .await;
+    state
+        .add_user("pat", "correct horse", UserStatus::PendingVerification)
+        .await;
+    let router = state.into_router();
+    let csrf = support::csrf(&router).await;
+
+    let invalid = "Invalid display id or password";
+    let cases = [
+        ("alice", "correct horse", StatusCode::SEE_OTHER, ""),
+        ("alice", "wrong horse", StatusCode::UNAUTHORIZED, invalid),
+        ("nobody", "correct horse", StatusCode::UNAUTHORIZED, invalid),
+        // the status of an account is only told once its password is known
+        ("sam", "wrong horse", StatusCode::UNAUTHORIZED, invalid),
+        (
+            "sam",
+            "correct horse",
+            StatusCode::FORBIDDEN,
+            "Account is suspended",
+        ),
+        (
+            "pat",
+            "correct horse",
+            StatusCode::FORBIDDEN,
+            "Account is pending verification",
+        ),
+    ];
+    for (display_id, password, status, message) in cases {
+        let res = support::login(&router, &csrf, display_id, password).await;
+        assert_eq!(res.status(), status, "{display_id} {password}");
+        let body = support::text(res).await;
+        assert!(body.starts_with(message), "{display_id} {password}: {body}");
+    }
+}
+
+#[tokio::test]
+async fn failed_logins_are_shown_to_their_account() {
+    let state = support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+    let alice = state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    let router = state.into_router();
+    let csrf = support::csrf(&router).await;
+    let res = support::login(&router, &csrf, "alice", "wrong horse").await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = support::login(&router, &csrf, "nobody", "wrong horse").await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = support::login(&router, &csrf, "alice", "correct horse").await;
+    let session = support::session_cookie(&res);
+    let res = support::get(&router, "/api/me/audit-events", &session).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let events: Vec<AuditEvent> = serde_json::from_str(&support::text(res).await).unwrap();
+    let failures: Vec<_> = events
+        .iter()
+        .filter(|e| e.kind == AuditEventKind::LoginFailure)
+        .collect();
+    assert_eq!(failures.len(), 1, "{events:?}");
+    let failure = failures[0];
+    assert_eq!(failure.actor, None);
+    assert_eq!(failure.subject, Some(alice.id));
+    let detail = failure.detail.as_deref().unwrap_or_default();
+    assert!(detail.contains("alice"), "{detail}");
+    assert!(!detail.contains("wrong horse"), "{detail}");
+}
@@ -0,0 +1,101 @@
+//! Checks through the router that failed logins take as long whether the user is unknown, the
+//! password is wrong, or the account is suspended.
+//!
+//! These compare wall-clock times, so they are ignored by default; run them on a quiet machine
+//! with `cargo test --test login_timing -- --ignored`.
+
+mod support;
+
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use login_with_axum::entity::UserStatus;
+use login_with_axum::password::{Algorithm, PasswordHasher};
+
+const SAMPLES: usize = 40;
+/// Allowed relative difference between the quantiles of two failure modes.
+const TOLERANCE: f64 = 0.25;
+
+fn hasher(algorithm: Algorithm) -> PasswordHasher {
+    // cheap enough for unoptimized builds, yet well above the cost of the rest of a login
+    let params = argon2::Params::new(1024, 1, 1, None).expect("argon2 params");
+    PasswordHasher::new(algorithm)
+        .bcrypt_cost(6)
+        .argon2_params(params)
+}
+
+fn quantile(sorted: &[Duration], q: usize) -> Duration {
+    sorted[(sorted.len() - 1) * q / 100]
+}
+
+async fn check(algorithm: Algorithm) {
+    let state = support::MemoryState::new(hasher(algorithm));
+    state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    state
+        .add_user("sam", "correct horse", UserStatus::Suspended)
+        .await;
+    let router = state.into_router();
+    let csrf = support::csrf(&router).await;
+
+    let cases = [
+        (
+            "wrong password",
+            "alice",
+            "wrong horse",
+            StatusCode::UNAUTHORIZED,
+        ),
+        (
+            "unknown user",
+            "nobody",
+            "wrong horse",
+            StatusCode::UNAUTHORIZED,
+        ),
+        (
+            "suspended user",
+            "sam",
+            "correct horse",
+            StatusCode::FORBIDDEN,
+        ),
+    ];
+    let mut times = vec![Vec::with_capacity(SAMPLES); cases.len()];
+    // interleaved, so that drift in machine load hits every case alike
+    for _ in 0..SAMPLES {
+        for ((_, display_id, password, status), times) in cases.iter().zip(&mut times) {
+            let start = Instant::now();
+            let res = support::login(&router, &csrf, display_id, password).await;
+            times.push(start.elapsed());
+            assert_eq!(res.status(), *status);
+        }
+    }
+    for times in &mut times {
+        times.sort_unstable();
+    }
+
+    let (baseline, rest) = times.split_first().expect("cases");
+    for ((name, ..), times) in cases[1..].iter().zip(rest) {
+        for q in [25, 50, 75] {
+            let a = quantile(baseline, q).as_secs_f64();
+            let b = quantile(times, q).as_secs_f64();
+            let diff = (a - b).abs() / a;
+            assert!(
+                diff < TOLERANCE,
+                "{algorithm:?} p{q}: wrong password {a:.4}s, {name} {b:.4}s ({:.0}% apart)",
+                diff * 100.0
+            );
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "timing-sensitive"]
+async fn failed_logins_take_as_long_with_argon2() {
+    check(Algorithm::Argon2id).await;
+}
+
+#[tokio::test]
+#[ignore = "timing-sensitive"]
+async fn failed_logins_take_as_long_with_bcrypt() {
+    check(Algorithm::Bcrypt).await;
+}
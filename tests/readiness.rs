@@ -0,0 +1,29 @@
+//! Probes through the router: readiness fails once draining starts, liveness does not.
+
+mod support;
+
+use axum::http::StatusCode;
+use login_with_axum::health::HealthCheck as _;
+use login_with_axum::password::{Algorithm, PasswordHasher};
+
+#[tokio::test]
+async fn draining_fails_readiness_only() {
+    let state = support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+    let readiness = state.readiness().clone();
+    let router = state.into_router();
+    assert_eq!(
+        support::get(&router, "/readyz", "").await.status(),
+        StatusCode::OK
+    );
+
+    readiness.drain();
+    let res = support::get(&router, "/readyz", "").await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let report: serde_json::Value = serde_json::from_str(&support::text(res).await).unwrap();
+    assert_eq!(report["ready"], false);
+    assert_eq!(report["draining"], true);
+    assert_eq!(
+        support::get(&router, "/healthz", "").await.status(),
+        StatusCode::OK
+    );
+}
@@ -0,0 +1,45 @@
+//! Request IDs through the router: taken from well-formed `X-Request-Id` headers or generated,
+//! and echoed on responses and error bodies.
+
+mod support;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use login_with_axum::password::{Algorithm, PasswordHasher};
+
+fn router() -> axum::Router {
+    support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4)).into_router()
+}
+
+async fn me(router: &axum::Router, request_id: Option<&str>) -> (String, String) {
+    let mut req = Request::get("/api/me");
+    if let Some(id) = request_id {
+        req = req.header("x-request-id", id);
+    }
+    let res = support::send(router, req.body(Body::empty()).unwrap()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let echoed = res.headers()["x-request-id"].to_str().unwrap().to_string();
+    (echoed, support::text(res).await)
+}
+
+#[tokio::test]
+async fn well_formed_ids_are_kept() {
+    let router = router();
+    let (echoed, body) = me(&router, Some("edge-7f3a:42")).await;
+    assert_eq!(echoed, "edge-7f3a:42");
+    assert!(body.ends_with("(request id edge-7f3a:42)"), "{body}");
+}
+
+#[tokio::test]
+async fn other_ids_are_replaced() {
+    let router = router();
+    let long = "a".repeat(65);
+    for incoming in [None, Some("two words"), Some("../etc"), Some(long.as_str())] {
+        let (echoed, body) = me(&router, incoming).await;
+        assert!(
+            uuid::Uuid::parse_str(&echoed).is_ok(),
+            "{incoming:?}: {echoed}"
+        );
+        assert!(body.ends_with(&format!("(request id {echoed})")), "{body}");
+    }
+}
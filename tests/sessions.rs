@@ -0,0 +1,65 @@
+//! Credential checks through the router: sessions and account status outlive the JWT's claims.
+
+mod support;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use login_with_axum::entity::{SetUserStatusParams, User, UserRepository as _, UserStatus};
+use login_with_axum::password::{Algorithm, PasswordHasher};
+
+async fn state() -> (support::MemoryState, User) {
+    let state = support::MemoryState::new(PasswordHasher::new(Algorithm::Bcrypt).bcrypt_cost(4));
+    let alice = state
+        .add_user("alice", "correct horse", UserStatus::Active)
+        .await;
+    (state, alice)
+}
+
+#[tokio::test]
+async fn logged_out_sessions_are_rejected() {
+    let router = state().await.0.into_router();
+    let csrf = support::csrf(&router).await;
+    let session =
+        support::session_cookie(&support::login(&router, &csrf, "alice", "correct horse").await);
+    let res = support::get(&router, "/api/me", &session).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let (token, csrf_cookie) = &csrf;
+    let req = Request::post("/api/logout")
+        .header(header::COOKIE, format!("{csrf_cookie}; {session}"))
+        .header("x-csrf-token", token)
+        .body(Body::empty())
+        .unwrap();
+    let res = support::send(&router, req).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    // the JWT is still unexpired, but its session is gone
+    let res = support::get(&router, "/api/me", &session).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn sessions_of_suspended_users_are_rejected() {
+    let (state, alice) = state().await;
+    let store = state.store();
+    let router = state.into_router();
+    let csrf = support::csrf(&router).await;
+    let res = support::login(&router, &csrf, "alice", "correct horse").await;
+    let session = support::session_cookie(&res);
+
+    let params = SetUserStatusParams {
+        id: alice.id,
+        status: UserStatus::Suspended,
+    };
+    store.set_user_status((), params).await.unwrap();
+    let res = support::get(&router, "/api/me", &session).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let params = SetUserStatusParams {
+        id: alice.id,
+        status: UserStatus::Active,
+    };
+    store.set_user_status((), params).await.unwrap();
+    let res = support::get(&router, "/api/me", &session).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
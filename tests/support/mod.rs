@@ -0,0 +1,633 @@
+//! An in-memory state for driving the real router without a database.
+//!
+//! Users, passwords, sessions and audit events live in memory; hashing goes through the same
+//! [`HashingPool`] as the server, and the registry, credential manager and router are the
+//! crate's own.
+#![allow(dead_code, reason = "each test target uses a part")]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use axum::body::Body;
+use axum::http::{Request, Response, header};
+use login_with_axum::entity::{
+    AuditEvent, CreateRoleParams, CreateSessionParams, CreateUserParams, DeleteSessionParams,
+    GetUserParams, GetUsersParams, GrantRoleParams, RecordAuditEventParams, RevokeRoleParams, Role,
+    SaveUserPasswordParams, SearchAuditEventsParams, Session, SessionId, SessionWithUserStatus,
+    SetUserStatusParams, TouchSessionParams, UpdateUserParams, User, UserId, UserPage, UserStatus,
+    VerifyUserPasswordParams,
+};
+use login_with_axum::error::Failure;
+use login_with_axum::password::{HashingPool, PasswordHasher};
+use login_with_axum::{
+    Assets, CookiePolicy, CorsPolicy, Limits, Registry, Secret, SecurityPolicy, entity,
+};
+use tower::ServiceExt as _;
+
+/// Users, their password hashes, their sessions and the audit events about them.
+#[derive(Debug)]
+pub struct Store {
+    hasher: HashingPool,
+    users: Mutex<HashMap<UserId, User>>,
+    passwords: Mutex<HashMap<UserId, String>>,
+    sessions: Mutex<HashMap<SessionId, Session>>,
+    audit_events: Mutex<Vec<AuditEvent>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Store {
+    fn find(&self, params: &GetUserParams) -> Result<User, Failure> {
+        let users = lock(&self.users);
+        let user = match params {
+            GetUserParams::ById(id) => users.get(id),
+            GetUserParams::ByDisplayId(display_id) => {
+                users.values().find(|u| &u.display_id == display_id)
+            }
+        };
+        user.cloned()
+            .ok_or_else(|| Failure::not_found("User not found"))
+    }
+}
+
+impl entity::UserRepository<()> for Store {
+    async fn get_users(&self, _ctx: (), params: GetUsersParams) -> Result<UserPage, Failure> {
+        let mut users: Vec<_> = lock(&self.users)
+            .values()
+            .filter(|u| params.include_deleted || u.status != UserStatus::Deleted)
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.display_id.cmp(&b.display_id));
+        let total = Some(users.len() as u64);
+        users.truncate(params.limit as usize);
+        Ok(UserPage {
+            users,
+            total,
+            next_cursor: None,
+        })
+    }
+
+    async fn get_user(&self, _ctx: (), params: GetUserParams) -> Result<User, Failure> {
+        self.find(&params)
+    }
+
+    async fn create_user(&self, _ctx: (), params: CreateUserParams) -> Result<User, Failure> {
+        let CreateUserParams {
+            display_id,
+            name,
+            raw_password,
+        } = params;
+        let psk = self.hasher.hash(raw_password).await?;
+        let user = User {
+            id: UserId(uuid::Uuid::new_v4()),
+            display_id,
+            name,
+            status: UserStatus::Active,
+            status_updated_at: chrono::Utc::now(),
+        };
+        let mut users = lock(&self.users);
+        if users.values().any(|u| u.display_id == user.display_id) {
+            return Err(Failure::conflict("Display ID already taken"));
+        }
+        users.insert(user.id, user.clone());
+        lock(&self.passwords).insert(user.id, psk);
+        Ok(user)
+    }
+
+    async fn update_user(&self, _ctx: (), params: UpdateUserParams) -> Result<User, Failure> {
+        if let Some(display_id) = &params.display_id {
+            User::check_display_id(display_id)?;
+        }
+        if let Some(name) = &params.name {
+            User::check_name(name)?;
+        }
+        let mut users = lock(&self.users);
+        let taken = |display_id: &String| {
+            users
+                .values()
+                .any(|u| u.id != params.id && &u.display_id == display_id)
+        };
+        if params.display_id.as_ref().is_some_and(taken) {
+            return Err(Failure::conflict(
+                "A user with the same display id already exists",
+            ));
+        }
+        let user = users
+            .get_mut(&params.id)
+            .ok_or_else(|| Failure::not_found("User not found"))?;
+        if let Some(display_id) = params.display_id {
+            user.display_id = display_id;
+        }
+        if let Some(name) = params.name {
+            user.name = name;
+        }
+        Ok(user.clone())
+    }
+
+    async fn set_user_status(
+        &self,
+        _ctx: (),
+        params: SetUserStatusParams,
+    ) -> Result<User, Failure> {
+        let mut users = lock(&self.users);
+        let user = users
+            .get_mut(&params.id)
+            .ok_or_else(|| Failure::not_found("User not found"))?;
+        user.status = params.status;
+        user.status_updated_at = chrono::Utc::now();
+        Ok(user.clone())
+    }
+
+    async fn delete_user(&self, ctx: (), id: UserId) -> Result<(), Failure> {
+        let params = SetUserStatusParams {
+            id,
+            status: UserStatus::Deleted,
+        };
+        self.set_user_status(ctx, params).await?;
+        lock(&self.passwords).remove(&id);
+        lock(&self.sessions).retain(|_, s| s.user_id != id);
+        Ok(())
+    }
+
+    async fn purge_deleted_users(
+        &self,
+        _ctx: (),
+        deleted_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, Failure> {
+        let mut users = lock(&self.users);
+        let before = users.len();
+        users.retain(|_, u| {
+            u.status != UserStatus::Deleted || u.status_updated_at >= deleted_before
+        });
+        Ok((before - users.len()) as u64)
+    }
+}
+
+impl entity::UserPasswordRepository<()> for Store {
+    async fn save_user_password(
+        &self,
+        _ctx: (),
+        params: SaveUserPasswordParams,
+    ) -> Result<(), Failure> {
+        let psk = self.hasher.hash(params.raw).await?;
+        lock(&self.passwords).insert(params.user_id, psk);
+        Ok(())
+    }
+
+    async fn verify_user_password(
+        &self,
+        _ctx: (),
+        params: VerifyUserPasswordParams,
+    ) -> Result<bool, Failure> {
+        let psk = lock(&self.passwords).get(&params.user_id).cloned();
+        let Some(psk) = psk else {
+            self.hasher.verify_dummy(params.raw).await?;
+            return Ok(false);
+        };
+        let verification = self.hasher.verify(params.raw, psk).await?;
+        Ok(verification.matches)
+    }
+}
+
+impl entity::RoleRepository<()> for Store {
+    async fn get_roles(&self, _ctx: ()) -> Result<Vec<Role>, Failure> {
+        Ok(Vec::new())
+    }
+
+    async fn get_user_roles(&self, _ctx: (), _user_id: UserId) -> Result<Vec<Role>, Failure> {
+        Ok(Vec::new())
+    }
+
+    async fn create_role(&self, _ctx: (), _params: CreateRoleParams) -> Result<Role, Failure> {
+        Err(Failure::bad_request("Roles are not supported"))
+    }
+
+    async fn grant_role(&self, _ctx: (), _params: GrantRoleParams) -> Result<(), Failure> {
+        Err(Failure::not_found("Role not found"))
+    }
+
+    async fn revoke_role(&self, _ctx: (), _params: RevokeRoleParams) -> Result<(), Failure> {
+        Err(Failure::not_found("Role not found"))
+    }
+}
+
+impl entity::SessionRepository<()> for Store {
+    async fn create_session(
+        &self,
+        _ctx: (),
+        params: CreateSessionParams,
+    ) -> Result<Session, Failure> {
+        let now = chrono::Utc::now();
+        let session = Session {
+            id: SessionId(uuid::Uuid::new_v4()),
+            user_id: params.user_id,
+            user_agent: params.user_agent,
+            ip: params.ip,
+            created_at: now,
+            last_used_at: now,
+            expires_at: params.expires_at,
+        };
+        lock(&self.sessions).insert(session.id, session.clone());
+        Ok(session)
+    }
+
+    async fn get_session(&self, _ctx: (), id: SessionId) -> Result<Session, Failure> {
+        lock(&self.sessions)
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Failure::not_found("Session not found"))
+    }
+
+    async fn get_session_with_user_status(
+        &self,
+        ctx: (),
+        id: SessionId,
+    ) -> Result<SessionWithUserStatus, Failure> {
+        let session = self.get_session(ctx, id).await?;
+        let user_status = self.find(&GetUserParams::ById(session.user_id))?.status;
+        Ok(SessionWithUserStatus {
+            session,
+            user_status,
+        })
+    }
+
+    async fn get_user_sessions(&self, _ctx: (), user_id: UserId) -> Result<Vec<Session>, Failure> {
+        let mut sessions: Vec<_> = lock(&self.sessions)
+            .values()
+            .filter(|s| s.user_id == user_id)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_used_at));
+        Ok(sessions)
+    }
+
+    async fn touch_session(&self, _ctx: (), params: TouchSessionParams) -> Result<(), Failure> {
+        if let Some(session) = lock(&self.sessions).get_mut(&params.id) {
+            session.last_used_at = params.used_at;
+        }
+        Ok(())
+    }
+
+    async fn delete_session(&self, _ctx: (), params: DeleteSessionParams) -> Result<(), Failure> {
+        let mut sessions = lock(&self.sessions);
+        match sessions.get(&params.id) {
+            Some(s) if s.user_id == params.user_id => {
+                sessions.remove(&params.id);
+                Ok(())
+            }
+            _ => Err(Failure::not_found("Session not found")),
+        }
+    }
+
+    async fn delete_user_sessions(&self, _ctx: (), user_id: UserId) -> Result<u64, Failure> {
+        let mut sessions = lock(&self.sessions);
+        let before = sessions.len();
+        sessions.retain(|_, s| s.user_id != user_id);
+        Ok((before - sessions.len()) as u64)
+    }
+}
+
+impl entity::AuditLog<()> for Store {
+    async fn record_audit_event(
+        &self,
+        _ctx: (),
+        params: RecordAuditEventParams,
+    ) -> Result<(), Failure> {
+        let RecordAuditEventParams {
+            kind,
+            actor,
+            subject,
+            detail,
+            ip,
+            user_agent,
+            request_id,
+        } = params;
+        let mut events = lock(&self.audit_events);
+        let event = AuditEvent {
+            id: events.len() as u64 + 1,
+            occurred_at: chrono::Utc::now(),
+            kind,
+            actor,
+            subject,
+            detail,
+            ip,
+            user_agent,
+            request_id,
+        };
+        events.push(event);
+        Ok(())
+    }
+
+    async fn search_audit_events(
+        &self,
+        _ctx: (),
+        params: SearchAuditEventsParams,
+    ) -> Result<Vec<AuditEvent>, Failure> {
+        let involves = |e: &AuditEvent| {
+            params
+                .user_id
+                .is_none_or(|id| e.actor == Some(id) || e.subject == Some(id))
+        };
+        Ok(lock(&self.audit_events)
+            .iter()
+            .rev()
+            .filter(|e| involves(e) && params.kind.is_none_or(|kind| e.kind == kind))
+            .filter(|e| params.before.is_none_or(|before| e.id < before))
+            .take(params.limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+// MARK: MemoryState
+
+/// A [`login_with_axum::State`] stand-in backed by a [`Store`].
+pub struct MemoryState {
+    store: Arc<Store>,
+    password_policy: login_with_axum::password::Policy,
+    registry: Registry,
+    jwt: login_with_axum::token::Jwt,
+    readiness: login_with_axum::health::Readiness,
+    pub cookie_policy: CookiePolicy,
+    cors: CorsPolicy,
+    limits: Limits,
+    pub trusted_proxies: login_with_axum::proxy::TrustedProxies,
+    assets: Assets,
+    security: SecurityPolicy,
+}
+
+impl MemoryState {
+    pub fn new(hasher: PasswordHasher) -> Self {
+        let jwt = login_with_axum::token::Jwt::builder()
+            .key("0123456789abcdef0123456789abcdef")
+            .issuer("test")
+            .lifetime(std::time::Duration::from_hours(1))
+            .build();
+        Self {
+            store: Arc::new(Store {
+                hasher: HashingPool::new(hasher, 2, 1024).expect("hashing pool"),
+                users: Mutex::default(),
+                passwords: Mutex::default(),
+                sessions: Mutex::default(),
+                audit_events: Mutex::default(),
+            }),
+            password_policy: login_with_axum::password::Policy::new(),
+            registry: Registry::new(),
+            jwt,
+            readiness: login_with_axum::health::Readiness::new(),
+            cookie_policy: CookiePolicy::default(),
+            cors: CorsPolicy::default(),
+            limits: Limits::default(),
+            trusted_proxies: login_with_axum::proxy::TrustedProxies::default(),
+            assets: Assets::default(),
+            security: SecurityPolicy::default(),
+        }
+    }
+
+    /// Adds an account with `status`, skipping the password policy and display id rules.
+    pub async fn add_user(&self, display_id: &str, password: &str, status: UserStatus) -> User {
+        use entity::UserRepository as _;
+
+        let params = CreateUserParams {
+            display_id: display_id.to_string(),
+            name: display_id.to_string(),
+            raw_password: Secret::from(password),
+        };
+        let user = self.store.create_user((), params).await.expect("add user");
+        let params = SetUserStatusParams {
+            id: user.id,
+            status,
+        };
+        self.store
+            .set_user_status((), params)
+            .await
+            .expect("set status")
+    }
+
+    /// The store behind the state, to change it once the state is moved into a router.
+    pub fn store(&self) -> Arc<Store> {
+        Arc::clone(&self.store)
+    }
+
+    pub fn into_router(self) -> axum::Router {
+        login_with_axum::make_router(Arc::new(self))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Ctx<'a>(&'a MemoryState);
+
+macro_rules! provide_store {
+    ($provide:ident, $repository:ident, $getter:ident) => {
+        impl entity::$provide for Ctx<'_> {
+            type Context<'b>
+                = ()
+            where
+                Self: 'b;
+            type $repository<'b>
+                = Store
+            where
+                Self: 'b;
+
+            fn context(&self) -> Self::Context<'_> {}
+            fn $getter(&self) -> &Self::$repository<'_> {
+                &self.0.store
+            }
+        }
+    };
+}
+
+provide_store!(ProvideUserRepository, UserRepository, user_repository);
+provide_store!(
+    ProvideUserPasswordRepository,
+    UserPasswordRepository,
+    user_password_repository
+);
+provide_store!(ProvideRoleRepository, RoleRepository, role_repository);
+provide_store!(
+    ProvideSessionRepository,
+    SessionRepository,
+    session_repository
+);
+
+impl entity::ProvidePasswordPolicy for Ctx<'_> {
+    type Context<'b>
+        = ()
+    where
+        Self: 'b;
+    type PasswordPolicy<'b>
+        = login_with_axum::password::Policy
+    where
+        Self: 'b;
+
+    fn context(&self) -> Self::Context<'_> {}
+    fn password_policy(&self) -> &Self::PasswordPolicy<'_> {
+        &self.0.password_policy
+    }
+}
+
+impl entity::ProvideUserRegistry for MemoryState {
+    type Context<'a> = Ctx<'a>;
+    type UserRegistry<'a> = Registry;
+
+    fn context(&self) -> Self::Context<'_> {
+        Ctx(self)
+    }
+    fn user_registry(&self) -> &Self::UserRegistry<'_> {
+        &self.registry
+    }
+}
+
+impl entity::ProvideCredentialManager for MemoryState {
+    type Context<'a> = Ctx<'a>;
+    type CredentialManager<'a> = login_with_axum::token::Jwt;
+
+    fn context(&self) -> Self::Context<'_> {
+        Ctx(self)
+    }
+    fn credential_manager(&self) -> &Self::CredentialManager<'_> {
+        &self.jwt
+    }
+}
+
+impl entity::ProvideAuditLog for MemoryState {
+    type Context<'a> = ();
+    type AuditLog<'a> = Store;
+
+    fn context(&self) -> Self::Context<'_> {}
+    fn audit_log(&self) -> &Self::AuditLog<'_> {
+        &self.store
+    }
+}
+
+impl login_with_axum::health::HealthCheck for MemoryState {
+    fn readiness(&self) -> &login_with_axum::health::Readiness {
+        &self.readiness
+    }
+
+    async fn check_readiness(&self) -> login_with_axum::health::ReadinessReport {
+        login_with_axum::health::ReadinessReport::new(self.readiness.is_draining(), Vec::new())
+    }
+}
+
+impl login_with_axum::RouteConfig for MemoryState {
+    fn cookie_name(&self) -> &'static str {
+        "session"
+    }
+    fn path_prefix(&self) -> &'static str {
+        "/"
+    }
+    fn cookie_policy(&self) -> &CookiePolicy {
+        &self.cookie_policy
+    }
+    fn trusted_origins(&self) -> &[String] {
+        &[]
+    }
+    fn cors(&self) -> &CorsPolicy {
+        &self.cors
+    }
+    fn limits(&self) -> &Limits {
+        &self.limits
+    }
+    fn trusted_proxies(&self) -> &login_with_axum::proxy::TrustedProxies {
+        &self.trusted_proxies
+    }
+    fn assets(&self) -> &Assets {
+        &self.assets
+    }
+    fn security(&self) -> &SecurityPolicy {
+        &self.security
+    }
+}
+
+// MARK: Requests
+
+/// The `name=value` pairs of the cookies a response sets.
+pub fn set_cookies(res: &Response<Body>) -> Vec<String> {
+    res.headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok()?.split(';').next().map(str::to_string))
+        .collect()
+}
+
+/// Sends `req` through a clone of `router`.
+pub async fn send(router: &axum::Router, req: Request<Body>) -> Response<Body> {
+    router.clone().oneshot(req).await.unwrap()
+}
+
+/// GETs `path` with the `cookie` header.
+pub async fn get(router: &axum::Router, path: &str, cookie: &str) -> Response<Body> {
+    let req = Request::get(path)
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    send(router, req).await
+}
+
+/// The session cookie of a successful login, as `name=value`.
+pub fn session_cookie(res: &Response<Body>) -> String {
+    set_cookies(res)
+        .into_iter()
+        .find(|c| c.starts_with("session="))
+        .expect("session cookie")
+}
+
+/// Fetches a CSRF token, returning it with the cookie it is checked against.
+pub async fn csrf(router: &axum::Router) -> (String, String) {
+    let req = Request::get("/api/csrf-token").body(Body::empty()).unwrap();
+    let res = router.clone().oneshot(req).await.unwrap();
+    let cookie = set_cookies(&res).pop().expect("CSRF cookie");
+    let (_, token) = cookie.split_once('=').expect("cookie value");
+    (token.to_string(), cookie)
+}
+
+/// Posts the login form, as the login page would.
+pub async fn login(
+    router: &axum::Router,
+    (token, cookie): &(String, String),
+    display_id: &str,
+    password: &str,
+) -> Response<Body> {
+    let form: String = form_urlencoded::Serializer::new(String::new())
+        .append_pair("display_id", display_id)
+        .append_pair("password", password)
+        .append_pair("csrf_token", token)
+        .finish();
+    let req = Request::post("/api/login")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::COOKIE, cookie)
+        .body(Body::from(form))
+        .unwrap();
+    router.clone().oneshot(req).await.unwrap()
+}
+
+/// Sends a JSON `body` with the session and CSRF cookies, as the client pages would.
+pub async fn send_json(
+    router: &axum::Router,
+    method: &str,
+    path: &str,
+    (token, cookie): &(String, String),
+    session: &str,
+    body: &serde_json::Value,
+) -> Response<Body> {
+    let req = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::COOKIE, format!("{cookie}; {session}"))
+        .header("x-csrf-token", token)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(router, req).await
+}
+
+/// The body of `res` as text.
+pub async fn text(res: Response<Body>) -> String {
+    let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    String::from_utf8(bytes.to_vec()).expect("UTF-8 body")
+}
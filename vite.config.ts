@@ -16,6 +16,7 @@ export default defineConfig({
         rollupOptions: {
             input: {
                 index: resolve(cwd, "client/index.html"),
+                notFound: resolve(cwd, "client/404.html"),
                 login: resolve(cwd, "client/login.html"),
                 me: resolve(cwd, "client/me.html"),
                 signup: resolve(cwd, "client/signup.html"),